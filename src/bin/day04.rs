@@ -1,27 +1,47 @@
-use anyhow::{anyhow, Context, Result};
-use std::collections::HashSet;
+use anyhow::{anyhow, bail, Context, Result};
 use std::fmt::{Debug, Display, Formatter};
 use std::str::FromStr;
 
 // close your eyes, nothing to see here <3
 fn main() -> anyhow::Result<()> {
     let contents = include_str!("../../inputs/day04.txt");
-    let (instructions, mut boards) = parse(contents)?;
+    let (instructions, boards) = parse(contents)?;
+
+    let mut args = std::env::args().skip(1);
+    let mut rules = Rules::default();
+    let mut print_log = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--win" => {
+                rules = args
+                    .next()
+                    .with_context(|| anyhow!("Expected win conditions, like rows,columns"))?
+                    .parse()?
+            }
+            "--log" => print_log = true,
+            _ => bail!("Unknown argument '{}'", arg),
+        }
+    }
+
+    rules.check(&boards)?;
+
+    let log = play(&instructions.0, &boards, &rules);
 
-    let score =
-        part1(&instructions.0, &mut boards).with_context(|| anyhow!("No winning board found!"))?;
+    if print_log {
+        log.iter().for_each(|win| println!("{}", win));
+    }
+
+    let score = part1(&log).with_context(|| anyhow!("No winning board found!"))?;
     println!("(day 04) part 1: {}", score);
 
-    boards.iter_mut().for_each(|board| board.reset());
-    let score =
-        part2(&instructions.0, &mut boards).with_context(|| anyhow!("No winning board found!"))?;
+    let score = part2(&log).with_context(|| anyhow!("No winning board found!"))?;
     println!("(day 04) part 2: {}", score);
 
     Ok(())
 }
 
 const CHUNK_SEPARATOR: &str = "\n\n";
-const LEN: usize = 5;
 
 fn parse(input: &str) -> Result<(Instructions, Vec<Board>)> {
     let (instructions, board) = input
@@ -32,48 +52,87 @@ fn parse(input: &str) -> Result<(Instructions, Vec<Board>)> {
 
     let board = board
         .split(CHUNK_SEPARATOR)
-        .map(Board::from_str)
+        .enumerate()
+        .map(|(n, board)| {
+            Board::from_str(board).with_context(|| anyhow!("Unable to parse board {}", n + 1))
+        })
         .collect::<Result<Vec<_>>>()?;
 
     Ok((instructions, board))
 }
 
-fn part1(instructions: &[u16], boards: &mut [Board]) -> Option<u16> {
-    for instr in instructions {
-        for board in boards.iter_mut() {
-            board.mark(*instr);
+// The score of the first board to win
+fn part1(log: &[Win]) -> Option<u32> {
+    log.first().map(|win| win.score)
+}
 
-            if board.is_winner() {
-                println!("first winner\n{}", &board);
+// The score of the last board to win
+fn part2(log: &[Win]) -> Option<u32> {
+    log.last().map(|win| win.score)
+}
 
-                return Some(board.unmarked_score() * instr);
+/// Plays the whole game, drawing each number in turn and marking it on every board which hasn't
+/// won yet. Returns the wins in the order in which they happened; boards which win on the same
+/// draw are in the order of the input.
+///
+/// The boards themselves are left as they were, we play on copies.
+fn play(instructions: &[u16], boards: &[Board], rules: &Rules) -> Vec<Win> {
+    let mut playing = boards.iter().cloned().enumerate().collect::<Vec<_>>();
+    let mut log = Vec::new();
+
+    for (draw, &number) in instructions.iter().enumerate() {
+        for (_, board) in playing.iter_mut() {
+            board.mark(number);
+        }
+
+        playing.retain(|(n, board)| match board.winning_line(rules) {
+            Some(line) => {
+                log.push(Win {
+                    board: *n,
+                    draw,
+                    number,
+                    line,
+                    score: board.unmarked_score() * u32::from(number),
+                });
+
+                false
             }
+            None => true,
+        });
+
+        if playing.is_empty() {
+            break;
         }
     }
 
-    None
+    log
 }
 
-fn part2(instructions: &[u16], boards: &mut [Board]) -> Option<u16> {
-    let mut queue = (0..boards.len()).collect::<HashSet<_>>();
-
-    for instr in instructions {
-        for (n, board) in boards.iter_mut().enumerate() {
-            board.mark(*instr);
-
-            if board.is_winner() {
-                queue.remove(&n);
-
-                if queue.is_empty() {
-                    println!("last winner\n{}", &board);
+/// An entry in the log of a game
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Win {
+    // index of the board in the input
+    board: usize,
+    // index of the draw in the instructions
+    draw: usize,
+    // the number which was drawn
+    number: u16,
+    line: Line,
+    score: u32,
+}
 
-                    return Some(board.unmarked_score() * instr);
-                }
-            }
-        }
+impl Display for Win {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "draw {} ({}): board {} wins with {}, scoring {}",
+            self.draw + 1,
+            self.number,
+            self.board + 1,
+            self.line,
+            self.score
+        )
     }
-
-    None
 }
 
 #[derive(Debug)]
@@ -95,27 +154,179 @@ impl FromStr for Instructions {
     }
 }
 
-#[derive(Default, Debug)]
+/// The ways in which a board can win
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Pattern {
+    Rows,
+    Columns,
+    // Both diagonals, only on square boards
+    Diagonals,
+    Corners,
+    // Every number on the board
+    Blackout,
+}
+
+impl FromStr for Pattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "rows" => Ok(Self::Rows),
+            "columns" => Ok(Self::Columns),
+            "diagonals" => Ok(Self::Diagonals),
+            "corners" => Ok(Self::Corners),
+            "blackout" => Ok(Self::Blackout),
+            _ => bail!(
+                "Unknown win condition '{}', expected one of: rows, columns, diagonals, corners, blackout",
+                s
+            ),
+        }
+    }
+}
+
+/// The patterns which make a board win, when all of their numbers are marked
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Rules(Vec<Pattern>);
+
+impl Rules {
+    fn new(patterns: Vec<Pattern>) -> Self {
+        Self(patterns)
+    }
+
+    // Diagonals are only defined for square boards
+    fn check(&self, boards: &[Board]) -> Result<()> {
+        if self.0.contains(&Pattern::Diagonals) {
+            if let Some((n, board)) = boards
+                .iter()
+                .enumerate()
+                .find(|(_, board)| board.width != board.height)
+            {
+                bail!(
+                    "Board {} is {}x{}, but diagonals can only win on square boards",
+                    n + 1,
+                    board.width,
+                    board.height
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Like the puzzle
+impl Default for Rules {
+    fn default() -> Self {
+        Self::new(vec![Pattern::Rows, Pattern::Columns])
+    }
+}
+
+impl FromStr for Rules {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let patterns = s
+            .split(',')
+            .map(Pattern::from_str)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self::new(patterns))
+    }
+}
+
+/// A set of numbers on a board which wins if they're all marked
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Line {
+    Row(usize),
+    Column(usize),
+    // From the top left to the bottom right
+    Diagonal,
+    // From the top right to the bottom left
+    AntiDiagonal,
+    Corners,
+    Blackout,
+}
+
+impl Display for Line {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Row(row) => write!(f, "row {}", row + 1),
+            Self::Column(col) => write!(f, "column {}", col + 1),
+            Self::Diagonal => f.write_str("the diagonal"),
+            Self::AntiDiagonal => f.write_str("the anti-diagonal"),
+            Self::Corners => f.write_str("the four corners"),
+            Self::Blackout => f.write_str("a blackout"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 struct Board {
-    // 5x5 board, row-major
-    cells: [Cell; LEN * LEN],
+    // row-major
+    cells: Vec<Cell>,
+    width: usize,
+    height: usize,
+}
+
+// An empty board of the size of the puzzle
+impl Default for Board {
+    fn default() -> Self {
+        Self {
+            cells: vec![Cell::default(); 5 * 5],
+            width: 5,
+            height: 5,
+        }
+    }
 }
 
 impl FromStr for Board {
     type Err = anyhow::Error;
 
     fn from_str(items: &str) -> Result<Self> {
-        let items = items
-            .split_ascii_whitespace()
-            .filter_map(|value| value.parse::<u16>().ok());
+        let rows = items
+            .lines()
+            .enumerate()
+            .map(|(y, line)| {
+                line.split_ascii_whitespace()
+                    .map(|value| {
+                        value.parse::<u16>().with_context(|| {
+                            anyhow!("Unable to parse '{}' on row {} as a number", value, y + 1)
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
 
-        let mut board = [Cell::default(); 25];
+        let width = rows.first().map(|row| row.len()).unwrap_or(0);
+
+        if width == 0 {
+            bail!("A board must have at least one number");
+        }
 
-        for (n, value) in items.enumerate() {
-            board[n].value = value;
+        if let Some((y, row)) = rows.iter().enumerate().find(|(_, row)| row.len() != width) {
+            bail!(
+                "Row {} has {} numbers, but the first row has {}",
+                y + 1,
+                row.len(),
+                width
+            );
         }
 
-        Ok(Self { cells: board })
+        let height = rows.len();
+        let cells = rows
+            .into_iter()
+            .flatten()
+            .map(|value| Cell {
+                value,
+                marked: false,
+            })
+            .collect();
+
+        Ok(Self {
+            cells,
+            width,
+            height,
+        })
     }
 }
 
@@ -125,65 +336,56 @@ impl Board {
             .iter_mut()
             .filter(|cell| cell.value == value)
             .for_each(|cell| cell.marked = true);
-
-        for cell in self.cells.iter_mut() {
-            if cell.value == value {
-                cell.marked = true;
-            }
-        }
     }
 
-    pub fn is_winner(&self) -> bool {
-        self.has_winning_row() || self.has_winning_column()
+    // The first line which is fully marked, if any, trying the patterns in order of the rules
+    pub fn winning_line(&self, rules: &Rules) -> Option<Line> {
+        rules
+            .0
+            .iter()
+            .flat_map(|pattern| self.lines(*pattern))
+            .find(|line| self.is_complete(*line))
     }
 
-    pub fn unmarked_score(&self) -> u16 {
+    pub fn unmarked_score(&self) -> u32 {
         self.cells
             .iter()
             .filter(|cell| !cell.marked)
-            .map(|cell| cell.value)
+            .map(|cell| u32::from(cell.value))
             .sum()
     }
 
-    pub fn reset(&mut self) {
-        for cell in self.cells.iter_mut() {
-            cell.marked = false;
-        }
-    }
-
-    fn has_winning_row(&self) -> bool {
-        let mut index = std::iter::successors(Some(0usize), |y| {
-            let next = y + 5;
-            if next >= LEN * LEN {
-                return None;
+    fn lines(&self, pattern: Pattern) -> Vec<Line> {
+        match pattern {
+            Pattern::Rows => (0..self.height).map(Line::Row).collect(),
+            Pattern::Columns => (0..self.width).map(Line::Column).collect(),
+            Pattern::Diagonals if self.width == self.height => {
+                vec![Line::Diagonal, Line::AntiDiagonal]
             }
-
-            Some(next)
-        });
-
-        // check whether a full row is marked, if it is, the board is winning
-        index.any(|a| Self::is_winning_row(&self.cells, a))
-    }
-
-    fn is_winning_row(cells: &[Cell; LEN * LEN], row: usize) -> bool {
-        (row..row + 5).all(|v| cells[v].marked)
+            Pattern::Diagonals => vec![],
+            Pattern::Corners => vec![Line::Corners],
+            Pattern::Blackout => vec![Line::Blackout],
+        }
     }
 
-    fn has_winning_column(&self) -> bool {
-        let mut index = std::iter::successors(Some(0usize), |x| {
-            if *x >= 4 {
-                return None;
-            }
-            Some(x + 1)
-        });
-
-        // check whether a full column is marked, if it is, the board is winning
-        index.any(|a| Self::is_winning_column(&self.cells, a))
+    // The indices of the cells on the line
+    fn cells_of(&self, line: Line) -> Vec<usize> {
+        let (width, height) = (self.width, self.height);
+
+        match line {
+            Line::Row(row) => (row * width..(row + 1) * width).collect(),
+            Line::Column(col) => (0..height).map(|row| row * width + col).collect(),
+            Line::Diagonal => (0..width).map(|i| i * width + i).collect(),
+            Line::AntiDiagonal => (0..width).map(|i| i * width + (width - 1 - i)).collect(),
+            Line::Corners => vec![0, width - 1, (height - 1) * width, height * width - 1],
+            Line::Blackout => (0..width * height).collect(),
+        }
     }
 
-    fn is_winning_column(cells: &[Cell; LEN * LEN], col: usize) -> bool {
-        // too many off by one errors, so hand generated instead <3
-        [0, 5, 10, 15, 20].iter().all(|v| cells[*v + col].marked)
+    fn is_complete(&self, line: Line) -> bool {
+        self.cells_of(line)
+            .into_iter()
+            .all(|index| self.cells[index].marked)
     }
 }
 
@@ -191,14 +393,8 @@ impl Display for Board {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let mut table = comfy_table::Table::new();
 
-        for row in (0..LEN * LEN).step_by(5) {
-            table.add_row(vec![
-                comfy_table::Cell::new(self.cells[row]),
-                comfy_table::Cell::new(self.cells[row + 1]),
-                comfy_table::Cell::new(self.cells[row + 2]),
-                comfy_table::Cell::new(self.cells[row + 3]),
-                comfy_table::Cell::new(self.cells[row + 4]),
-            ]);
+        for row in self.cells.chunks(self.width) {
+            table.add_row(row.iter().map(comfy_table::Cell::new));
         }
 
         std::fmt::Display::fmt(&table, f)
@@ -238,7 +434,8 @@ impl Display for Cell {
 
 #[cfg(test)]
 mod tests {
-    use crate::{parse, part1, part2, Board};
+    use crate::{parse, part1, part2, play, Board, Line, Pattern, Rules, Win};
+    use std::str::FromStr;
 
     yare::ide!();
 
@@ -258,7 +455,12 @@ mod tests {
         board.cells[which[3]].marked = true;
         board.cells[which[4]].marked = true;
 
-        assert!(board.has_winning_row());
+        assert!(board
+            .winning_line(&Rules::new(vec![Pattern::Rows]))
+            .is_some());
+        assert!(board
+            .winning_line(&Rules::new(vec![Pattern::Columns]))
+            .is_none());
     }
 
     #[yare::parameterized(
@@ -277,14 +479,19 @@ mod tests {
         board.cells[which[3]].marked = true;
         board.cells[which[4]].marked = true;
 
-        assert!(board.has_winning_column());
+        assert!(board
+            .winning_line(&Rules::new(vec![Pattern::Columns]))
+            .is_some());
+        assert!(board
+            .winning_line(&Rules::new(vec![Pattern::Rows]))
+            .is_none());
     }
 
     #[test]
     fn is_not_winning_row() {
         let mut board = Board::default();
         board.cells[0].marked = true;
-        let no = Board::is_winning_row(&board.cells, 0);
+        let no = board.is_complete(Line::Row(0));
 
         assert!(!no)
     }
@@ -297,7 +504,7 @@ mod tests {
         board.cells[2].marked = true;
         board.cells[3].marked = true;
         board.cells[4].marked = true;
-        let yes = Board::is_winning_row(&board.cells, 0);
+        let yes = board.is_complete(Line::Row(0));
 
         println!("{}", board);
 
@@ -307,8 +514,9 @@ mod tests {
     #[test]
     fn example_part1() {
         let input = include_str!("../../inputs/example/day04.txt");
-        let (instructions, mut boards) = parse(input).unwrap();
-        let score = part1(&instructions.0, &mut boards).unwrap();
+        let (instructions, boards) = parse(input).unwrap();
+        let log = play(&instructions.0, &boards, &Rules::default());
+        let score = part1(&log).unwrap();
 
         assert_eq!(score, 4512);
     }
@@ -316,9 +524,157 @@ mod tests {
     #[test]
     fn example_part2() {
         let input = include_str!("../../inputs/example/day04.txt");
-        let (instructions, mut boards) = parse(input).unwrap();
-        let score = part2(&instructions.0, &mut boards).unwrap();
+        let (instructions, boards) = parse(input).unwrap();
+        let log = play(&instructions.0, &boards, &Rules::default());
+        let score = part2(&log).unwrap();
 
         assert_eq!(score, 1924);
     }
+
+    #[test]
+    fn rectangular_board() {
+        let mut board = Board::from_str("1 2 3\n4 5 6").unwrap();
+        assert_eq!((board.width, board.height), (3, 2));
+
+        [3, 6].iter().for_each(|&value| board.mark(value));
+        assert_eq!(board.winning_line(&Rules::default()), Some(Line::Column(2)));
+
+        [4, 5].iter().for_each(|&value| board.mark(value));
+        assert_eq!(board.winning_line(&Rules::default()), Some(Line::Row(1)));
+        assert_eq!(board.unmarked_score(), 3);
+    }
+
+    #[test]
+    fn invalid_boards() {
+        // too few numbers on a row
+        assert!(Board::from_str("1 2 3\n4 5").is_err());
+        // too many
+        assert!(Board::from_str("1 2\n3 4\n5 6 7").is_err());
+        assert!(Board::from_str("1 2\n3 x").is_err());
+        assert!(Board::from_str("").is_err());
+
+        let input = include_str!("../../inputs/example/day04.txt");
+        let input = input.replacen("19", "", 1);
+        assert!(parse(&input).is_err());
+    }
+
+    #[test]
+    fn diagonals() {
+        let rules = Rules::from_str("diagonals").unwrap();
+
+        let mut board = Board::from_str("1 2 3\n4 5 6\n7 8 9").unwrap();
+        [1, 5].iter().for_each(|&value| board.mark(value));
+        assert!(board.winning_line(&rules).is_none());
+
+        [9].iter().for_each(|&value| board.mark(value));
+        assert_eq!(board.winning_line(&rules), Some(Line::Diagonal));
+
+        let mut board = Board::from_str("1 2 3\n4 5 6\n7 8 9").unwrap();
+        [3, 5, 7].iter().for_each(|&value| board.mark(value));
+        assert_eq!(board.winning_line(&rules), Some(Line::AntiDiagonal));
+        assert!(board.winning_line(&Rules::default()).is_none());
+
+        let boards = [Board::from_str("1 2 3\n4 5 6").unwrap()];
+        assert!(rules.check(&boards).is_err());
+        assert!(Rules::default().check(&boards).is_ok());
+    }
+
+    #[test]
+    fn corners_and_blackout() {
+        let rules = Rules::from_str("corners,blackout").unwrap();
+
+        let mut board = Board::from_str("1 2 3\n4 5 6").unwrap();
+        [1, 3, 4].iter().for_each(|&value| board.mark(value));
+        assert!(board.winning_line(&rules).is_none());
+
+        board.mark(6);
+        assert_eq!(board.winning_line(&rules), Some(Line::Corners));
+
+        let rules = Rules::from_str("blackout").unwrap();
+        assert!(board.winning_line(&rules).is_none());
+
+        [2, 5].iter().for_each(|&value| board.mark(value));
+        assert_eq!(board.winning_line(&rules), Some(Line::Blackout));
+        assert_eq!(board.unmarked_score(), 0);
+    }
+
+    #[test]
+    fn example_blackout() {
+        // every number on the boards is drawn eventually, so the boards are all fully marked,
+        // leaving nothing unmarked to score
+        let input = include_str!("../../inputs/example/day04.txt");
+        let (instructions, boards) = parse(input).unwrap();
+        let rules = Rules::from_str("blackout").unwrap();
+        let log = play(&instructions.0, &boards, &rules);
+
+        assert_eq!(part2(&log), Some(0));
+    }
+
+    #[test]
+    fn unknown_rules() {
+        assert!(Rules::from_str("rows,sideways").is_err());
+        assert!(Rules::from_str("").is_err());
+    }
+
+    #[test]
+    fn example_log() {
+        let input = include_str!("../../inputs/example/day04.txt");
+        let (instructions, boards) = parse(input).unwrap();
+        let log = play(&instructions.0, &boards, &Rules::default());
+
+        // the third board wins first, on its top row, the second board wins last, on 13
+        assert_eq!(
+            log[0],
+            Win {
+                board: 2,
+                draw: 11,
+                number: 24,
+                line: Line::Row(0),
+                score: 4512
+            }
+        );
+        assert_eq!(
+            log[0].to_string(),
+            "draw 12 (24): board 3 wins with row 1, scoring 4512"
+        );
+
+        assert_eq!(log.len(), 3);
+        assert_eq!((log[2].board, log[2].number, log[2].score), (1, 13, 1924));
+
+        // every board wins once, in order of the draws
+        let mut winners = log.iter().map(|win| win.board).collect::<Vec<_>>();
+        winners.sort_unstable();
+        assert_eq!(winners, vec![0, 1, 2]);
+        assert!(log.windows(2).all(|w| w[0].draw <= w[1].draw));
+
+        // the game is played on copies, so the boards can be played again as they are
+        assert!(boards.iter().all(|board| board.unmarked_score() > 0));
+        assert_eq!(play(&instructions.0, &boards, &Rules::default()), log);
+    }
+
+    #[test]
+    fn no_winner() {
+        let boards = vec![Board::from_str("1 2\n3 4").unwrap()];
+        let log = play(&[1, 4], &boards, &Rules::default());
+
+        assert!(log.is_empty());
+        assert_eq!(part1(&log), None);
+        assert_eq!(part2(&log), None);
+    }
+
+    #[test]
+    fn same_draw() {
+        // both boards win when 2 is drawn, in the order of the input
+        let boards = vec![
+            Board::from_str("1 2\n3 4").unwrap(),
+            Board::from_str("5 6\n2 1").unwrap(),
+        ];
+        let log = play(&[1, 2, 3], &boards, &Rules::default());
+
+        let wins = log
+            .iter()
+            .map(|win| (win.board, win.draw, win.line))
+            .collect::<Vec<_>>();
+        assert_eq!(wins, vec![(0, 1, Line::Row(0)), (1, 1, Line::Row(1))]);
+    }
 }
@@ -1,56 +1,666 @@
-use anyhow::{anyhow, Context};
-use std::collections::HashMap;
+use anyhow::{anyhow, bail, Context};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Debug, Formatter};
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::str::FromStr;
 
 fn main() -> anyhow::Result<()> {
     let contents = include_str!("../../inputs/day05.txt");
     let inputs = parse(contents)?;
 
-    let part1 = part1(inputs.iter());
+    let mut args = std::env::args().skip(1);
+    let mut backend = Backend::Sparse;
+    let mut rasterization = Rasterization::Octilinear;
+    let mut queries = Vec::new();
+    let (mut print, mut pgm, mut ppm) = (false, None, None);
+
+    let coord = |arg: Option<String>| -> anyhow::Result<Coord> {
+        arg.with_context(|| anyhow!("Expected a coordinate, like 1,2"))?
+            .parse()
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--backend" => {
+                backend = args
+                    .next()
+                    .with_context(|| anyhow!("Expected a backend"))?
+                    .parse()?
+            }
+            "--lines" => {
+                rasterization = args
+                    .next()
+                    .with_context(|| anyhow!("Expected a rasterization mode"))?
+                    .parse()?
+            }
+            "--coverage" => queries.push(Query::Coverage(coord(args.next())?)),
+            "--cells" => {
+                let area = Bounds::new(coord(args.next())?, coord(args.next())?);
+                let threshold = args
+                    .next()
+                    .with_context(|| anyhow!("Expected a threshold"))?;
+                let threshold = threshold
+                    .parse()
+                    .with_context(|| anyhow!("Unable to parse '{}' as a number", threshold))?;
+
+                queries.push(Query::CellsAtLeast(area, threshold));
+            }
+            "--through" => queries.push(Query::VentsThrough(coord(args.next())?)),
+            "--histogram" => queries.push(Query::Histogram),
+            "--print" => print = true,
+            "--pgm" => pgm = Some(args.next().with_context(|| anyhow!("Expected a file"))?),
+            "--ppm" => ppm = Some(args.next().with_context(|| anyhow!("Expected a file"))?),
+            _ => bail!("Unknown argument '{}'", arg),
+        }
+    }
+
+    // The sweep line only handles horizontal and vertical vents, so everything beyond part 1
+    // falls back to drawing the vents on a map
+    let drawing = match backend {
+        Backend::SweepLine => {
+            eprintln!("(day 05) warning: the sweep line only solves part 1, using a dense map");
+            Backend::Dense
+        }
+        backend => backend,
+    };
+
+    let part1 = part1(inputs.iter(), backend)?;
     println!("(day 05) part 1: {}", part1);
 
-    let part2 = part2(inputs.iter());
+    let part2 = part2(inputs.iter(), drawing, rasterization)?;
     println!("(day 05) part 2: {}", part2);
 
+    if !queries.is_empty() {
+        let survey = Survey::new(inputs.iter(), drawing, rasterization)?;
+        queries.iter().for_each(|query| query.answer(&survey));
+    }
+
+    if print || pgm.is_some() || ppm.is_some() {
+        let map = drawing.draw(inputs.iter(), rasterization)?;
+        let fmt = VentMapFmt::new(map.as_ref());
+
+        if print {
+            print!("{}", fmt);
+        }
+
+        let create = |path: &str| -> anyhow::Result<BufWriter<File>> {
+            let file =
+                File::create(path).with_context(|| anyhow!("Unable to create '{}'", path))?;
+            Ok(BufWriter::new(file))
+        };
+
+        if let Some(path) = pgm {
+            fmt.write_pgm(&mut create(&path)?)?;
+        }
+
+        if let Some(path) = ppm {
+            fmt.write_ppm(&mut create(&path)?)?;
+        }
+    }
+
     Ok(())
 }
 
-fn part1<'vent>(vents: impl Iterator<Item = &'vent HydrothermalVent>) -> usize {
+fn part1<'vent>(
+    vents: impl Iterator<Item = &'vent HydrothermalVent>,
+    backend: Backend,
+) -> anyhow::Result<usize> {
     // Solution idea:
     // - Create an empty map, with on each coordinate a natural number, representing how often a
     //   a line covers that point.
     // - For each vent, 'draw' it onto the map, incrementing each cell which the vent covers.
     // - To find the number of points where to cells overlap, we iterate the map, filter
     //   where the cell value is >= 2, and count the residual.
+    //
+    // Which map we draw on depends on the backend: an HashMap saves storage where there are no
+    // lines, assuming the map is sparse, while a contiguous array sized to the vents is faster.
+    // For horizontal and vertical lines only, we can also skip drawing altogether, see
+    // `sweep_line`.
+    let orthogonal = vents.filter(|vent| vent.is_orthogonal());
+    backend.count_overlaps(orthogonal, Rasterization::Octilinear)
+}
+
+fn part2<'vent>(
+    vents: impl Iterator<Item = &'vent HydrothermalVent>,
+    backend: Backend,
+    rasterization: Rasterization,
+) -> anyhow::Result<usize> {
+    // same for part 2 really, except we also need to draw diagonal lines, or lines at any
+    // angle, depending on the rasterization
+    backend.count_overlaps(vents, rasterization)
+}
+
+/// The way to count overlapping vents
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Backend {
+    // Draw on a `SparseMap`
+    Sparse,
+    // Draw on a `DenseMap`
+    Dense,
+    // Don't draw at all, only for horizontal and vertical vents
+    SweepLine,
+}
+
+impl Backend {
+    fn count_overlaps<'vent>(
+        &self,
+        vents: impl Iterator<Item = &'vent HydrothermalVent>,
+        rasterization: Rasterization,
+    ) -> anyhow::Result<usize> {
+        match self {
+            // vents are either horizontal or vertical, or rejected, so there's nothing to
+            // rasterize
+            Self::SweepLine => sweep_line(vents),
+            _ => Ok(self.draw(vents, rasterization)?.overlaps()),
+        }
+    }
+
+    fn draw<'vent>(
+        &self,
+        mut vents: impl Iterator<Item = &'vent HydrothermalVent>,
+        rasterization: Rasterization,
+    ) -> anyhow::Result<Box<dyn VentMap>> {
+        match self {
+            Self::Sparse => {
+                let mut map = SparseMap::new();
+                vents.try_for_each(|vent| vent.draw_line(&mut map, rasterization))?;
+
+                Ok(Box::new(map))
+            }
+            Self::Dense => {
+                // we need to know how large the map will be before drawing
+                let vents = vents.collect::<Vec<_>>();
+                let mut map = DenseMap::new(Bounds::of(vents.iter().copied()))?;
+                vents
+                    .iter()
+                    .try_for_each(|vent| vent.draw_line(&mut map, rasterization))?;
+
+                Ok(Box::new(map))
+            }
+            Self::SweepLine => bail!("The sweep line doesn't draw a map"),
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sparse" => Ok(Self::Sparse),
+            "dense" => Ok(Self::Dense),
+            "sweep-line" => Ok(Self::SweepLine),
+            _ => bail!(
+                "Unknown backend '{}', expected one of: sparse, dense, sweep-line",
+                s
+            ),
+        }
+    }
+}
+
+/// The way to determine which points a vent covers
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Rasterization {
+    // Only horizontal, vertical and diagonal (45 degree) lines, covering every point between
+    // the ends, like the puzzle
+    Octilinear,
+    // Lines at any angle, covering only the points which lie exactly on the line
+    Lattice,
+    // Lines at any angle, covering the points closest to the line, one per step along its
+    // longest axis
+    Bresenham,
+}
+
+impl Rasterization {
+    fn check(&self, vent: &HydrothermalVent) -> anyhow::Result<()> {
+        let (dx, dy) = vent.delta();
+
+        match self {
+            Self::Octilinear if dx != 0 && dy != 0 && dx.abs() != dy.abs() => bail!(
+                "Vent {:?} is neither horizontal, vertical or diagonal, try another \
+                 rasterization mode",
+                vent
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl FromStr for Rasterization {
+    type Err = anyhow::Error;
 
-    // First, create an empty map, I'll use an HashMap for now, as we don't know what the largest
-    // coordinate components are. In addition, using an hashmap allows us to save storage where there
-    // are no lines, assuming the map will be sparse. We may leave some performance on the table versus
-    // a map which uses a contiguous array.
-    let mut map = VentMap::new();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "octilinear" => Ok(Self::Octilinear),
+            "lattice" => Ok(Self::Lattice),
+            "bresenham" => Ok(Self::Bresenham),
+            _ => bail!(
+                "Unknown rasterization mode '{}', expected one of: octilinear, lattice, bresenham",
+                s
+            ),
+        }
+    }
+}
+
+/// A map of how many vents cover each coordinate
+trait VentMap {
+    fn increment(&mut self, coord: Coord);
+
+    fn get(&self, coord: Coord) -> u32;
 
-    // Now we draw the vents on the map
-    vents.for_each(|vent| vent.draw_orthogonal_line(&mut map));
+    // The coordinates covered by at least one vent
+    fn cells(&self) -> Box<dyn Iterator<Item = (Coord, u32)> + '_>;
 
-    // println!("{}", VentMapFmt(&map));
+    // The coordinates within the area covered by at least one vent
+    fn cells_within(&self, area: Bounds) -> Box<dyn Iterator<Item = (Coord, u32)> + '_> {
+        Box::new(self.cells().filter(move |&(coord, _)| area.contains(coord)))
+    }
 
     // All left to do is find the cells which overlap, i.e. whose value is at least 2
-    map.iter().filter(|(_, value)| **value >= 2).count()
+    fn overlaps(&self) -> usize {
+        self.cells().filter(|&(_, value)| value >= 2).count()
+    }
+}
+
+type SparseMap = HashMap<Coord, u32>;
+
+impl VentMap for SparseMap {
+    fn increment(&mut self, coord: Coord) {
+        *self.entry(coord).or_default() += 1;
+    }
+
+    fn get(&self, coord: Coord) -> u32 {
+        HashMap::get(self, &coord).copied().unwrap_or_default()
+    }
+
+    fn cells(&self) -> Box<dyn Iterator<Item = (Coord, u32)> + '_> {
+        Box::new(self.iter().map(|(&coord, &value)| (coord, value)))
+    }
+}
+
+/// A map stored as a contiguous array, row by row, covering only the bounding box of the
+/// vents.
+struct DenseMap {
+    bounds: Bounds,
+    cells: Vec<u32>,
+}
+
+impl DenseMap {
+    fn new(bounds: Bounds) -> anyhow::Result<Self> {
+        let size = bounds
+            .width()
+            .checked_mul(bounds.height())
+            .with_context(|| {
+                anyhow!(
+                    "A dense map of {}x{} cells is too large, try the sparse backend",
+                    bounds.width(),
+                    bounds.height()
+                )
+            })?;
+
+        Ok(Self {
+            cells: vec![0; size],
+            bounds,
+        })
+    }
+
+    fn index(&self, coord: Coord) -> Option<usize> {
+        self.bounds.contains(coord).then(|| {
+            let x = Bounds::distance(self.bounds.min.x, coord.x) as usize;
+            let y = Bounds::distance(self.bounds.min.y, coord.y) as usize;
+
+            y * self.bounds.width() + x
+        })
+    }
+}
+
+impl VentMap for DenseMap {
+    fn increment(&mut self, coord: Coord) {
+        let index = self
+            .index(coord)
+            .unwrap_or_else(|| panic!("{:?} is outside of the map", coord));
+
+        self.cells[index] += 1;
+    }
+
+    fn get(&self, coord: Coord) -> u32 {
+        self.index(coord)
+            .map(|index| self.cells[index])
+            .unwrap_or_default()
+    }
+
+    fn cells(&self) -> Box<dyn Iterator<Item = (Coord, u32)> + '_> {
+        let width = self.bounds.width();
+
+        Box::new(
+            self.cells
+                .iter()
+                .enumerate()
+                .filter(|(_, &value)| value > 0)
+                .map(move |(index, &value)| {
+                    let x = (i64::from(self.bounds.min.x) + (index % width) as i64) as i32;
+                    let y = (i64::from(self.bounds.min.y) + (index / width) as i64) as i32;
+
+                    (Coord { x, y }, value)
+                }),
+        )
+    }
+
+    // Only look at the rows and columns of the area, instead of at every cell
+    fn cells_within(&self, area: Bounds) -> Box<dyn Iterator<Item = (Coord, u32)> + '_> {
+        let area = match self.bounds.intersection(&area) {
+            Some(area) => area,
+            None => return Box::new(std::iter::empty()),
+        };
+
+        Box::new(
+            (area.min.y..=area.max.y)
+                .flat_map(move |y| (area.min.x..=area.max.x).map(move |x| Coord { x, y }))
+                .map(move |coord| (coord, self.get(coord)))
+                .filter(|&(_, value)| value > 0),
+        )
+    }
+}
+
+/// The smallest rectangle containing a set of coordinates, including `min` and `max`.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+struct Bounds {
+    min: Coord,
+    max: Coord,
+}
+
+impl Bounds {
+    // The rectangle with the given corners
+    fn new(a: Coord, b: Coord) -> Self {
+        Self {
+            min: Coord {
+                x: a.x.min(b.x),
+                y: a.y.min(b.y),
+            },
+            max: Coord {
+                x: a.x.max(b.x),
+                y: a.y.max(b.y),
+            },
+        }
+    }
+
+    // Without any vents, we'll just have the origin
+    fn of<'vent>(vents: impl Iterator<Item = &'vent HydrothermalVent>) -> Self {
+        vents
+            .flat_map(|vent| [vent.from, vent.to])
+            .fold(None, |bounds: Option<Bounds>, coord| {
+                Some(match bounds {
+                    Some(Bounds { min, max }) => Bounds {
+                        min: Coord {
+                            x: min.x.min(coord.x),
+                            y: min.y.min(coord.y),
+                        },
+                        max: Coord {
+                            x: max.x.max(coord.x),
+                            y: max.y.max(coord.y),
+                        },
+                    },
+                    None => Bounds {
+                        min: coord,
+                        max: coord,
+                    },
+                })
+            })
+            .unwrap_or_default()
+    }
+
+    // Coordinates may span the whole range of an i32, so their distance is an i64
+    fn distance(from: i32, to: i32) -> i64 {
+        i64::from(to) - i64::from(from)
+    }
+
+    fn width(&self) -> usize {
+        (Self::distance(self.min.x, self.max.x) + 1) as usize
+    }
+
+    fn height(&self) -> usize {
+        (Self::distance(self.min.y, self.max.y) + 1) as usize
+    }
+
+    fn contains(&self, coord: Coord) -> bool {
+        (self.min.x..=self.max.x).contains(&coord.x) && (self.min.y..=self.max.y).contains(&coord.y)
+    }
+
+    fn intersection(&self, other: &Bounds) -> Option<Bounds> {
+        let min = Coord {
+            x: self.min.x.max(other.min.x),
+            y: self.min.y.max(other.min.y),
+        };
+        let max = Coord {
+            x: self.max.x.min(other.max.x),
+            y: self.max.y.min(other.max.y),
+        };
+
+        (min.x <= max.x && min.y <= max.y).then(|| Bounds { min, max })
+    }
+}
+
+/// A drawn vent map, which remembers the vents it was drawn from, to answer questions about
+/// specific places.
+struct Survey<'vent> {
+    map: Box<dyn VentMap>,
+    vents: Vec<&'vent HydrothermalVent>,
+    rasterization: Rasterization,
+}
+
+impl<'vent> Survey<'vent> {
+    fn new(
+        vents: impl Iterator<Item = &'vent HydrothermalVent>,
+        backend: Backend,
+        rasterization: Rasterization,
+    ) -> anyhow::Result<Self> {
+        let vents = vents.collect::<Vec<_>>();
+        let map = backend.draw(vents.iter().copied(), rasterization)?;
+
+        Ok(Self {
+            map,
+            vents,
+            rasterization,
+        })
+    }
+
+    // How many vents cover the coordinate
+    fn coverage(&self, coord: Coord) -> u32 {
+        self.map.get(coord)
+    }
+
+    // The coordinates within the area covered by at least `threshold` vents, row by row
+    fn cells_at_least(&self, area: Bounds, threshold: u32) -> Vec<(Coord, u32)> {
+        let mut cells = self
+            .map
+            .cells_within(area)
+            .filter(|&(_, value)| value >= threshold)
+            .collect::<Vec<_>>();
+
+        cells.sort_by_key(|(coord, _)| (coord.y, coord.x));
+        cells
+    }
+
+    // The vents which cover the coordinate
+    fn vents_through(&self, coord: Coord) -> Vec<&'vent HydrothermalVent> {
+        self.vents
+            .iter()
+            .filter(|vent| Bounds::new(vent.from, vent.to).contains(coord))
+            .filter(|vent| vent.points(self.rasterization).contains(&coord))
+            .copied()
+            .collect()
+    }
+
+    // For each amount of overlapping vents, at how many coordinates they overlap
+    fn histogram(&self) -> BTreeMap<u32, usize> {
+        self.map
+            .cells()
+            .fold(BTreeMap::new(), |mut histogram, (_, value)| {
+                *histogram.entry(value).or_default() += 1;
+                histogram
+            })
+    }
+}
+
+/// A question for the `Survey`
+enum Query {
+    Coverage(Coord),
+    CellsAtLeast(Bounds, u32),
+    VentsThrough(Coord),
+    Histogram,
+}
+
+impl Query {
+    fn answer(&self, survey: &Survey) {
+        match self {
+            Self::Coverage(coord) => {
+                println!("coverage at {:?}: {}", coord, survey.coverage(*coord))
+            }
+            Self::CellsAtLeast(area, threshold) => {
+                println!(
+                    "cells within {:?} covered at least {} times:",
+                    area, threshold
+                );
+                for (coord, value) in survey.cells_at_least(*area, *threshold) {
+                    println!("  {:?}: {}", coord, value);
+                }
+            }
+            Self::VentsThrough(coord) => {
+                println!("vents through {:?}:", coord);
+                for vent in survey.vents_through(*coord) {
+                    println!("  {:?}", vent);
+                }
+            }
+            Self::Histogram => {
+                println!("overlaps: cells");
+                for (value, count) in survey.histogram() {
+                    println!("  {}: {}", value, count);
+                }
+            }
+        }
+    }
 }
 
-fn part2<'vent>(vents: impl Iterator<Item = &'vent HydrothermalVent>) -> usize {
-    // same for part 2 really, except we also need to draw diagonal lines
-    let mut map = VentMap::new();
+/// Counts the overlaps of horizontal and vertical vents, without drawing them.
+///
+/// We sweep a vertical line from left to right, only stopping where a horizontal vent starts or
+/// ends, or where there's a vertical vent. Meanwhile, we keep track of how many horizontal vents
+/// are at each y. Between the stops nothing changes, so each column in between has the same
+/// amount of overlaps.
+fn sweep_line<'vent>(
+    vents: impl Iterator<Item = &'vent HydrothermalVent>,
+) -> anyhow::Result<usize> {
+    // per x, the changes to the number of horizontal vents at some y; the stops are i64, since
+    // a vent may end at the largest i32, and stop just after it
+    let mut events = BTreeMap::<i64, Vec<(i32, i32)>>::new();
+    // per x, the vertical vents as (min y, max y)
+    let mut vertical = HashMap::<i64, Vec<(i64, i64)>>::new();
+
+    for vent in vents {
+        let (from, to) = (vent.from, vent.to);
+
+        if from.y == to.y {
+            let (start, end) = (from.x.min(to.x), from.x.max(to.x));
+            events
+                .entry(i64::from(start))
+                .or_default()
+                .push((from.y, 1));
+            events
+                .entry(i64::from(end) + 1)
+                .or_default()
+                .push((from.y, -1));
+        } else if from.x == to.x {
+            events.entry(i64::from(from.x)).or_default();
+            vertical
+                .entry(i64::from(from.x))
+                .or_default()
+                .push((i64::from(from.y.min(to.y)), i64::from(from.y.max(to.y))));
+        } else {
+            bail!(
+                "The sweep line only supports horizontal and vertical vents, but found {:?}",
+                vent
+            );
+        }
+    }
+
+    // the number of horizontal vents at each y, and how many of those have at least 2
+    let mut horizontal = BTreeMap::<i32, i32>::new();
+    let mut overlapping = 0;
+
+    let mut count = 0;
+    let stops = events.into_iter().collect::<Vec<_>>();
 
-    vents.for_each(|vent| vent.draw_line(&mut map));
+    for (i, (x, changes)) in stops.iter().enumerate() {
+        for &(y, change) in changes {
+            let value = horizontal.entry(y).or_default();
 
-    // println!("{}", VentMapFmt(&map));
+            match (*value, *value + change) {
+                (1, 2) => overlapping += 1,
+                (2, 1) => overlapping -= 1,
+                _ => {}
+            }
 
-    map.iter().filter(|(_, value)| **value >= 2).count()
+            *value += change;
+
+            if *value == 0 {
+                horizontal.remove(&y);
+            }
+        }
+
+        // after the last stop, there are no more horizontal vents
+        let next = stops.get(i + 1).map(|(x, _)| *x).unwrap_or(x + 1);
+        let columns = (next - x) as usize;
+
+        count += match vertical.get(x) {
+            Some(intervals) => {
+                column_overlaps(intervals, &horizontal) + (columns - 1) * overlapping
+            }
+            None => columns * overlapping,
+        };
+    }
+
+    Ok(count)
 }
 
-type VentMap = HashMap<Coord, i32>;
+// The overlaps in a single column, with vertical vents covering the given intervals of y, and
+// horizontal vents crossing it
+fn column_overlaps(intervals: &[(i64, i64)], horizontal: &BTreeMap<i32, i32>) -> usize {
+    let mut changes = BTreeMap::<i64, i32>::new();
+
+    for &(start, end) in intervals {
+        *changes.entry(start).or_default() += 1;
+        *changes.entry(end + 1).or_default() -= 1;
+    }
+
+    // the number of vertical vents, from each y until the next
+    let segments = changes
+        .into_iter()
+        .scan(0, |covered, (y, change)| {
+            *covered += change;
+            Some((y, *covered))
+        })
+        .collect::<Vec<_>>();
+
+    let vertical_overlaps = segments
+        .windows(2)
+        .filter(|w| w[0].1 >= 2)
+        .map(|w| (w[1].0 - w[0].0) as usize)
+        .sum::<usize>();
+
+    // horizontal vents add overlaps only where there were none from the vertical vents alone
+    let crossing_overlaps = horizontal
+        .iter()
+        .filter(|&(&y, &value)| {
+            let segment = segments.partition_point(|&(start, _)| start <= i64::from(y));
+            let covered = segment.checked_sub(1).map(|i| segments[i].1).unwrap_or(0);
+
+            covered < 2 && covered + value >= 2
+        })
+        .count();
+
+    vertical_overlaps + crossing_overlaps
+}
 
 type HydrothermalVents = Vec<HydrothermalVent>;
 
@@ -68,32 +678,92 @@ struct HydrothermalVent {
 }
 
 impl HydrothermalVent {
-    // Draw only horizontal or vertical lines
-    fn draw_orthogonal_line(&self, map: &mut VentMap) {
-        if self.from.x == self.to.x || self.from.y == self.to.y {
-            self.draw_line(map)
-        }
+    // Only horizontal or vertical lines
+    fn is_orthogonal(&self) -> bool {
+        self.from.x == self.to.x || self.from.y == self.to.y
     }
 
-    // Computes the coordinates which are between `from` and `to`, including `from` and `to`,
-    // and draws them on the map; assumes horizontal, vertical or diagonal lines.
-    fn draw_line(&self, map: &mut VentMap) {
-        // slope
-        let dx = (self.to.x - self.from.x).signum();
-        let dy = (self.to.y - self.from.y).signum();
+    fn delta(&self) -> (i32, i32) {
+        (self.to.x - self.from.x, self.to.y - self.from.y)
+    }
 
-        let len = std::cmp::max(
-            (self.to.x - self.from.x).abs(),
-            (self.to.y - self.from.y).abs(),
-        );
+    // Draws the vent on the map, unless the rasterization can't represent it
+    fn draw_line(
+        &self,
+        map: &mut impl VentMap,
+        rasterization: Rasterization,
+    ) -> anyhow::Result<()> {
+        rasterization.check(self)?;
 
-        (0..=len).for_each(|n| {
-            *map.entry(Coord {
-                x: self.from.x + n * dx,
-                y: self.from.y + n * dy,
+        self.points(rasterization)
+            .into_iter()
+            .for_each(|coord| map.increment(coord));
+
+        Ok(())
+    }
+
+    // Computes the coordinates which are between `from` and `to`, including `from` and `to`;
+    // assumes the rasterization was checked to be able to represent the vent.
+    fn points(&self, rasterization: Rasterization) -> Vec<Coord> {
+        let (dx, dy) = self.delta();
+
+        // the steps to take along each axis, and how many
+        let (step_x, step_y, len) = match rasterization {
+            Rasterization::Octilinear => (dx.signum(), dy.signum(), dx.abs().max(dy.abs())),
+            Rasterization::Lattice => {
+                // the smallest step which lands exactly on another point of the line
+                let n = gcd(dx.abs(), dy.abs()).max(1);
+                (dx / n, dy / n, gcd(dx.abs(), dy.abs()))
+            }
+            Rasterization::Bresenham => return self.bresenham(),
+        };
+
+        (0..=len)
+            .map(|n| Coord {
+                x: self.from.x + n * step_x,
+                y: self.from.y + n * step_y,
             })
-            .or_default() += 1;
-        });
+            .collect()
+    }
+
+    // Works for any octant: we step along both axes, whichever keeps the accumulated error
+    // smallest, see https://en.wikipedia.org/wiki/Bresenham%27s_line_algorithm
+    fn bresenham(&self) -> Vec<Coord> {
+        let (dx, dy) = self.delta();
+        let (step_x, step_y) = (dx.signum(), dy.signum());
+        let (dx, dy) = (dx.abs(), -dy.abs());
+
+        let mut points = Vec::with_capacity(dx.max(-dy) as usize + 1);
+        let mut coord = self.from;
+        let mut error = dx + dy;
+
+        loop {
+            points.push(coord);
+
+            if coord == self.to {
+                return points;
+            }
+
+            let e2 = 2 * error;
+
+            if e2 >= dy {
+                error += dy;
+                coord.x += step_x;
+            }
+
+            if e2 <= dx {
+                error += dx;
+                coord.y += step_y;
+            }
+        }
+    }
+}
+
+fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
     }
 }
 
@@ -112,7 +782,7 @@ impl FromStr for HydrothermalVent {
     }
 }
 
-#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+#[derive(Debug, Default, Copy, Clone, Hash, Eq, PartialEq)]
 struct Coord {
     x: i32,
     y: i32,
@@ -138,68 +808,451 @@ impl FromStr for Coord {
     }
 }
 
-// Not necessary for the solution, just to print the map :)
-struct VentMapFmt<'map>(&'map VentMap);
+// Not necessary for the solution, just to look at the map :)
+//
+// Only the part of the map covered by vents is shown, so large coordinates don't produce huge
+// images or walls of dots.
+struct VentMapFmt<'map> {
+    map: &'map dyn VentMap,
+    bounds: Option<Bounds>,
+    max: u32,
+}
+
+impl<'map> VentMapFmt<'map> {
+    fn new(map: &'map dyn VentMap) -> Self {
+        let bounds = map
+            .cells()
+            .fold(None, |bounds: Option<Bounds>, (coord, _)| {
+                Some(match bounds {
+                    Some(bounds) => Bounds::new(
+                        Coord {
+                            x: bounds.min.x.min(coord.x),
+                            y: bounds.min.y.min(coord.y),
+                        },
+                        Coord {
+                            x: bounds.max.x.max(coord.x),
+                            y: bounds.max.y.max(coord.y),
+                        },
+                    ),
+                    None => Bounds::new(coord, coord),
+                })
+            });
+
+        let max = map.cells().map(|(_, value)| value).max().unwrap_or(0);
+
+        Self { map, bounds, max }
+    }
+
+    // The values row by row, with y going down like in the puzzle
+    fn rows(&self) -> anyhow::Result<(Bounds, impl Iterator<Item = Vec<u32>> + '_)> {
+        let bounds = self
+            .bounds
+            .with_context(|| anyhow!("No vents on the map, so there's nothing to export"))?;
+
+        let rows = (bounds.min.y..=bounds.max.y).map(move |y| {
+            (bounds.min.x..=bounds.max.x)
+                .map(|x| self.map.get(Coord { x, y }))
+                .collect()
+        });
+
+        Ok((bounds, rows))
+    }
+
+    // Grayscale, where the most overlapping vents are white
+    fn write_pgm<W: Write>(&self, output: &mut W) -> anyhow::Result<()> {
+        let (bounds, rows) = self.rows()?;
+
+        write!(output, "P5\n{} {}\n255\n", bounds.width(), bounds.height())
+            .with_context(|| anyhow!("Unable to write PGM header"))?;
+
+        for row in rows {
+            let line = row
+                .iter()
+                .map(|&value| (value * 255 / self.max) as u8)
+                .collect::<Vec<u8>>();
+
+            output
+                .write_all(&line)
+                .with_context(|| anyhow!("Unable to write PGM row"))?;
+        }
+
+        output
+            .flush()
+            .with_context(|| anyhow!("Unable to flush PGM image"))
+    }
+
+    // From blue, through green and yellow, to red for the most overlapping vents
+    fn write_ppm<W: Write>(&self, output: &mut W) -> anyhow::Result<()> {
+        let (bounds, rows) = self.rows()?;
+
+        write!(output, "P6\n{} {}\n255\n", bounds.width(), bounds.height())
+            .with_context(|| anyhow!("Unable to write PPM header"))?;
+
+        for row in rows {
+            let line = row
+                .iter()
+                .flat_map(|&value| self.color(value))
+                .collect::<Vec<u8>>();
+
+            output
+                .write_all(&line)
+                .with_context(|| anyhow!("Unable to write PPM row"))?;
+        }
+
+        output
+            .flush()
+            .with_context(|| anyhow!("Unable to flush PPM image"))
+    }
+
+    fn color(&self, value: u32) -> [u8; 3] {
+        const STOPS: [[f64; 3]; 4] = [
+            [0.0, 0.0, 255.0],
+            [0.0, 255.0, 0.0],
+            [255.0, 255.0, 0.0],
+            [255.0, 0.0, 0.0],
+        ];
+
+        if value == 0 {
+            return [0, 0, 0];
+        }
+
+        // a single vent is at the first stop, the most overlapping at the last
+        let t = match self.max {
+            1 => 0.0,
+            max => f64::from(value - 1) / f64::from(max - 1) * (STOPS.len() - 1) as f64,
+        };
+
+        let stop = (t.floor() as usize).min(STOPS.len() - 2);
+        let (from, to, t) = (STOPS[stop], STOPS[stop + 1], t - stop as f64);
+
+        [0, 1, 2].map(|i| (from[i] + (to[i] - from[i]) * t).round() as u8)
+    }
+}
 
+// Like the puzzle: a dot where there are no vents, otherwise the number of vents. Ten or more
+// vents don't fit in a single digit, so these are shown as '+'.
 impl std::fmt::Display for VentMapFmt<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let max_x = self.0.iter().map(|(coord, _)| coord.x).max().unwrap_or(0);
-
-        let max_y = self.0.iter().map(|(coord, _)| coord.y).max().unwrap_or(0);
-
-        // Can't collect directly into a table sadly :(
-        let mut table = comfy_table::Table::new();
-
-        let rows = (0..=max_y)
-            .map(|y| {
-                // Can't collect directly into a row sadly :(
-                let cells = (0..=max_x)
-                    .map(|x| {
-                        if let Some(val) = self.0.get(&Coord { x, y }) {
-                            comfy_table::Cell::new(val)
-                        } else {
-                            comfy_table::Cell::new("")
-                        }
-                    })
-                    .collect::<Vec<comfy_table::Cell>>();
-
-                let mut row = comfy_table::Row::new();
-
-                for cell in cells {
-                    row.add_cell(cell);
-                }
-
-                row
-            })
-            .collect::<Vec<comfy_table::Row>>();
+        let rows = match self.rows() {
+            Ok((_, rows)) => rows,
+            Err(_) => return Ok(()),
+        };
 
         for row in rows {
-            table.add_row(row);
+            let line = row
+                .iter()
+                .map(|&value| match value {
+                    0 => '.',
+                    1..=9 => char::from_digit(value, 10).unwrap(),
+                    _ => '+',
+                })
+                .collect::<String>();
+
+            writeln!(f, "{}", line)?;
         }
 
-        std::fmt::Display::fmt(&table, f)
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{parse, part1, part2};
+    use crate::{
+        parse, part1, part2, Backend, Bounds, Coord, DenseMap, HydrothermalVent, Rasterization,
+        Survey, VentMapFmt,
+    };
+    use std::collections::BTreeMap;
+
+    const BACKENDS: [Backend; 3] = [Backend::Sparse, Backend::Dense, Backend::SweepLine];
 
     #[test]
     fn part1_example() {
         let input = include_str!("../../inputs/example/day05.txt");
         let inputs = parse(input).unwrap();
-        let part1 = part1(inputs.iter());
 
-        assert_eq!(part1, 5);
+        for backend in BACKENDS {
+            let part1 = part1(inputs.iter(), backend).unwrap();
+            assert_eq!(part1, 5, "{:?}", backend);
+        }
     }
 
     #[test]
     fn part2_example() {
         let input = include_str!("../../inputs/example/day05.txt");
         let inputs = parse(input).unwrap();
-        let part2 = part2(inputs.iter());
 
-        assert_eq!(part2, 12);
+        for backend in [Backend::Sparse, Backend::Dense] {
+            let part2 = part2(inputs.iter(), backend, Rasterization::Octilinear).unwrap();
+            assert_eq!(part2, 12, "{:?}", backend);
+        }
+    }
+
+    #[test]
+    fn backends_agree() {
+        let input = include_str!("../../inputs/day05.txt");
+        let inputs = parse(input).unwrap();
+
+        let part1 = BACKENDS.map(|backend| part1(inputs.iter(), backend).unwrap());
+        assert!(
+            part1.iter().all(|&answer| answer == part1[0]),
+            "{:?}",
+            part1
+        );
+
+        let part2 = [Backend::Sparse, Backend::Dense]
+            .map(|backend| part2(inputs.iter(), backend, Rasterization::Octilinear).unwrap());
+        assert_eq!(part2, [19258; 2]);
+    }
+
+    #[test]
+    fn sweep_line_agrees_with_drawing() {
+        // many short vents in a small area, so that they overlap a lot, also end to end, and
+        // including single points
+        let mut state = 42u64;
+        let mut random = |n: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % n) as i32
+        };
+
+        for _ in 0..50 {
+            let vents = (0..40)
+                .map(|_| {
+                    let (x, y, len) = (random(20) - 5, random(20) - 5, random(8));
+                    let line = match random(2) {
+                        0 => format!("{},{} -> {},{}", x, y, x + len, y),
+                        _ => format!("{},{} -> {},{}", x, y + len, x, y),
+                    };
+
+                    line.parse::<HydrothermalVent>().unwrap()
+                })
+                .collect::<Vec<_>>();
+
+            let expected = part1(vents.iter(), Backend::Dense).unwrap();
+            assert_eq!(part1(vents.iter(), Backend::SweepLine).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn sweep_line_rejects_diagonals() {
+        let input = include_str!("../../inputs/example/day05.txt");
+        let inputs = parse(input).unwrap();
+
+        assert!(part2(inputs.iter(), Backend::SweepLine, Rasterization::Octilinear).is_err());
+    }
+
+    #[test]
+    fn extreme_coordinates() {
+        let bounds = Bounds {
+            min: Coord { x: i32::MIN, y: 0 },
+            max: Coord { x: i32::MAX, y: 1 },
+        };
+        assert_eq!((bounds.width(), bounds.height()), (1 << 32, 2));
+
+        let everything = Bounds {
+            min: Coord {
+                x: i32::MIN,
+                y: i32::MIN,
+            },
+            max: Coord {
+                x: i32::MAX,
+                y: i32::MAX,
+            },
+        };
+        assert!(DenseMap::new(everything).is_err());
+
+        // two vents in the corner, which cross in a single point
+        let input = "2147483645,-2147483648 -> 2147483647,-2147483648\n2147483647,-2147483648 -> 2147483647,-2147483646";
+        let inputs = parse(input).unwrap();
+
+        for backend in BACKENDS {
+            assert_eq!(part1(inputs.iter(), backend).unwrap(), 1, "{:?}", backend);
+        }
+    }
+
+    fn points(line: &str, rasterization: Rasterization) -> Vec<(i32, i32)> {
+        let vent = line.parse::<HydrothermalVent>().unwrap();
+
+        vent.points(rasterization)
+            .into_iter()
+            .map(|Coord { x, y }| (x, y))
+            .collect()
+    }
+
+    #[test]
+    fn rasterizations_agree_on_octilinear_lines() {
+        let input = include_str!("../../inputs/example/day05.txt");
+
+        for line in input.lines() {
+            let expected = points(line, Rasterization::Octilinear);
+
+            assert_eq!(points(line, Rasterization::Lattice), expected, "{}", line);
+            assert_eq!(points(line, Rasterization::Bresenham), expected, "{}", line);
+        }
+    }
+
+    #[test]
+    fn lattice_points() {
+        let expected = vec![(0, 0), (3, 2), (6, 4)];
+        assert_eq!(points("0,0 -> 6,4", Rasterization::Lattice), expected);
+
+        let expected = vec![(5, 1), (4, 4)];
+        assert_eq!(points("5,1 -> 4,4", Rasterization::Lattice), expected);
+
+        assert_eq!(points("2,2 -> 2,2", Rasterization::Lattice), vec![(2, 2)]);
+    }
+
+    #[test]
+    fn bresenham_points() {
+        let expected = vec![(0, 0), (1, 1), (2, 1), (3, 2), (4, 3), (5, 3), (6, 4)];
+        assert_eq!(points("0,0 -> 6,4", Rasterization::Bresenham), expected);
+
+        let expected = vec![(5, 1), (5, 2), (4, 3), (4, 4)];
+        assert_eq!(points("5,1 -> 4,4", Rasterization::Bresenham), expected);
+    }
+
+    #[test]
+    fn octilinear_rejects_other_angles() {
+        let inputs = parse("0,0 -> 6,4\n0,0 -> 4,4").unwrap();
+
+        for backend in [Backend::Sparse, Backend::Dense] {
+            assert!(part2(inputs.iter(), backend, Rasterization::Octilinear).is_err());
+
+            let lattice = part2(inputs.iter(), backend, Rasterization::Lattice).unwrap();
+            assert_eq!(lattice, 1);
+
+            let bresenham = part2(inputs.iter(), backend, Rasterization::Bresenham).unwrap();
+            assert_eq!(bresenham, 2);
+        }
+    }
+
+    #[test]
+    fn survey() {
+        let input = include_str!("../../inputs/example/day05.txt");
+        let inputs = parse(input).unwrap();
+
+        for backend in [Backend::Sparse, Backend::Dense] {
+            let survey = Survey::new(inputs.iter(), backend, Rasterization::Octilinear).unwrap();
+
+            assert_eq!(survey.coverage(Coord { x: 7, y: 1 }), 2);
+            assert_eq!(survey.coverage(Coord { x: 4, y: 4 }), 3);
+            assert_eq!(survey.coverage(Coord { x: 0, y: 1 }), 0);
+            assert_eq!(survey.coverage(Coord { x: -5, y: 100 }), 0);
+
+            let area = Bounds::new(Coord { x: 4, y: 4 }, Coord { x: 0, y: 0 });
+            let cells = survey.cells_at_least(area, 2);
+            let expected = vec![
+                (Coord { x: 2, y: 2 }, 2),
+                (Coord { x: 3, y: 4 }, 2),
+                (Coord { x: 4, y: 4 }, 3),
+            ];
+            assert_eq!(cells, expected);
+
+            // partially outside of the map
+            let area = Bounds::new(Coord { x: 8, y: 3 }, Coord { x: 20, y: 4 });
+            let cells = survey.cells_at_least(area, 1);
+            assert_eq!(cells.len(), 2);
+
+            let vents = survey
+                .vents_through(Coord { x: 4, y: 4 })
+                .iter()
+                .map(|vent| format!("{:?} -> {:?}", vent.from, vent.to))
+                .collect::<Vec<_>>();
+            let expected = vec![
+                "Coord { x: 8, y: 0 } -> Coord { x: 0, y: 8 }",
+                "Coord { x: 9, y: 4 } -> Coord { x: 3, y: 4 }",
+                "Coord { x: 0, y: 0 } -> Coord { x: 8, y: 8 }",
+            ];
+            assert_eq!(vents, expected);
+
+            let histogram = survey.histogram();
+            assert_eq!(histogram, BTreeMap::from([(1, 27), (2, 10), (3, 2)]));
+        }
+    }
+
+    #[test]
+    fn survey_needs_a_map() {
+        let input = include_str!("../../inputs/example/day05.txt");
+        let inputs = parse(input).unwrap();
+
+        assert!(Survey::new(inputs.iter(), Backend::SweepLine, Rasterization::Octilinear).is_err());
+    }
+
+    #[test]
+    fn print_example() {
+        let input = include_str!("../../inputs/example/day05.txt");
+        let inputs = parse(input).unwrap();
+
+        let map = Backend::Dense
+            .draw(inputs.iter(), Rasterization::Octilinear)
+            .unwrap();
+        let expected = "\
+1.1....11.
+.111...2..
+..2.1.111.
+...1.2.2..
+.112313211
+...1.2....
+..1...1...
+.1.....1..
+1.......1.
+222111....
+";
+
+        assert_eq!(VentMapFmt::new(map.as_ref()).to_string(), expected);
+    }
+
+    fn cropped() -> Box<dyn crate::VentMap> {
+        let inputs = parse("10,10 -> 12,10\n11,9 -> 11,11").unwrap();
+
+        Backend::Sparse
+            .draw(inputs.iter(), Rasterization::Octilinear)
+            .unwrap()
+    }
+
+    #[test]
+    fn print_cropped() {
+        let map = cropped();
+        assert_eq!(VentMapFmt::new(map.as_ref()).to_string(), ".1.\n121\n.1.\n");
+    }
+
+    #[test]
+    fn pgm_cropped() {
+        let map = cropped();
+        let mut pgm = Vec::new();
+        VentMapFmt::new(map.as_ref()).write_pgm(&mut pgm).unwrap();
+
+        let mut expected = b"P5\n3 3\n255\n".to_vec();
+        expected.extend([0, 127, 0, 127, 255, 127, 0, 127, 0]);
+        assert_eq!(pgm, expected);
+    }
+
+    #[test]
+    fn ppm_cropped() {
+        let map = cropped();
+        let mut ppm = Vec::new();
+        VentMapFmt::new(map.as_ref()).write_ppm(&mut ppm).unwrap();
+
+        let header = b"P6\n3 3\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+
+        let pixels = &ppm[header.len()..];
+        assert_eq!(pixels.len(), 3 * 3 * 3);
+        // no vents, a single vent, and the most overlapping vents
+        assert_eq!(&pixels[0..3], &[0, 0, 0]);
+        assert_eq!(&pixels[3..6], &[0, 0, 255]);
+        assert_eq!(&pixels[12..15], &[255, 0, 0]);
+    }
+
+    #[test]
+    fn export_empty() {
+        let map = Backend::Sparse
+            .draw(std::iter::empty(), Rasterization::Octilinear)
+            .unwrap();
+        let fmt = VentMapFmt::new(map.as_ref());
+
+        assert_eq!(fmt.to_string(), "");
+        assert!(fmt.write_pgm(&mut Vec::new()).is_err());
     }
 }
@@ -1,6 +1,8 @@
+use anyhow::{anyhow, Context};
 use comfy_table::{Cell, Row, Table};
 use std::collections::HashSet;
 use std::fmt::{Debug, Display, Formatter};
+use std::io::Write;
 
 fn main() -> anyhow::Result<()> {
     let contents = include_bytes!("../../inputs/day09.txt");
@@ -10,6 +12,24 @@ fn main() -> anyhow::Result<()> {
     let part2 = part2(contents);
     println!("(day 09) part 2: {}", part2);
 
+    // Optionally render the basins, either to the terminal (--render), or as an image (--ppm <path>)
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--render" => println!("{}", BasinMapFmt::new(contents)),
+            "--ppm" => {
+                let path = args
+                    .next()
+                    .with_context(|| anyhow!("Expected a path after '--ppm'"))?;
+                let file = std::fs::File::create(&path)
+                    .with_context(|| anyhow!("Unable to create '{}'", path))?;
+
+                BasinMapFmt::new(contents).write_ppm(&mut std::io::BufWriter::new(file), 4)?;
+            }
+            _ => anyhow::bail!("Unknown argument '{}'", arg),
+        }
+    }
+
     Ok(())
 }
 
@@ -180,6 +200,185 @@ impl<'b> Display for HeightMapFmt<'b> {
     }
 }
 
+/// Labels each cell with the basin it belongs to, or `None` for cells of height 9, which
+/// are not part of any basin. Basins are numbered in order of their low point.
+fn basins(map: &[u8], width: usize) -> Vec<Option<usize>> {
+    let mut labels = vec![None; map.len()];
+
+    (0..map.len())
+        .filter(|&index| is_low_point(width, map, index))
+        .enumerate()
+        .for_each(|(basin, low)| {
+            let mut seen = HashSet::new();
+            check_neighbours(map, low, width, &mut seen);
+
+            seen.into_iter().for_each(|index| {
+                labels[index].get_or_insert(basin);
+            });
+        });
+
+    labels
+}
+
+// Not necessary for the solution, but lets us inspect how the map is segmented into basins
+struct BasinMapFmt {
+    map: Vec<u8>,
+    width: usize,
+    basins: Vec<Option<usize>>,
+    low_points: HashSet<usize>,
+}
+
+impl BasinMapFmt {
+    fn new(input: &[u8]) -> Self {
+        let width = width(input);
+        let map = parse(input);
+        let basins = basins(&map, width);
+        let low_points = (0..map.len())
+            .filter(|&index| is_low_point(width, &map, index))
+            .collect();
+
+        Self {
+            map,
+            width,
+            basins,
+            low_points,
+        }
+    }
+
+    fn height(&self) -> usize {
+        self.map.len() / self.width
+    }
+
+    /// The colour of a cell; each basin gets its own hue, and lower cells are drawn brighter.
+    /// Ridges (height 9) are dark grey and low points white.
+    fn color(&self, index: usize) -> [u8; 3] {
+        if self.low_points.contains(&index) {
+            return [255, 255, 255];
+        }
+
+        match self.basins[index] {
+            Some(basin) => {
+                // Spread hues using the golden ratio, so basins with nearby numbers differ
+                let hue = (basin as f64 * 0.618_033_988_75).fract();
+                let value = 1.0 - f64::from(self.map[index]) / 12.0;
+                hsv_to_rgb(hue, 0.85, value)
+            }
+            None => [40, 40, 40],
+        }
+    }
+
+    /// Writes the map as a binary PPM (P6) image, where each cell is a `scale` by `scale` block
+    fn write_ppm<W: Write>(&self, output: &mut W, scale: usize) -> anyhow::Result<()> {
+        let (width, height) = (self.width * scale, self.height() * scale);
+        write!(output, "P6\n{} {}\n255\n", width, height)
+            .with_context(|| anyhow!("Unable to write PPM header"))?;
+
+        for row in 0..self.height() {
+            let line = (0..self.width)
+                .flat_map(|col| {
+                    let color = self.color(row * self.width + col);
+                    std::iter::repeat(color).take(scale)
+                })
+                .flatten()
+                .collect::<Vec<u8>>();
+
+            for _ in 0..scale {
+                output
+                    .write_all(&line)
+                    .with_context(|| anyhow!("Unable to write PPM pixel data"))?;
+            }
+        }
+
+        output
+            .flush()
+            .with_context(|| anyhow!("Unable to flush PPM image"))
+    }
+}
+
+impl Display for BasinMapFmt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, cells) in self.map.chunks(self.width).enumerate() {
+            for (j, value) in cells.iter().enumerate() {
+                let index = i * self.width + j;
+                let [r, g, b] = self.color(index);
+
+                if self.low_points.contains(&index) {
+                    // low points are printed in bold, and inverted
+                    write!(f, "\x1b[1;7m{}\x1b[0m", value)?;
+                } else {
+                    write!(f, "\x1b[38;2;{};{};{}m{}\x1b[0m", r, g, b, value)?;
+                }
+            }
+
+            f.write_str("\n")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn hsv_to_rgb(hue: f64, saturation: f64, value: f64) -> [u8; 3] {
+    let sector = hue * 6.0;
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let m = value - chroma;
+
+    let (r, g, b) = match sector as u8 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+
+    let channel = |c: f64| ((c + m) * 255.0).round() as u8;
+
+    [channel(r), channel(g), channel(b)]
+}
+
+#[cfg(test)]
+mod tests_render {
+    use crate::{basins, parse, width, BasinMapFmt};
+
+    #[test]
+    fn basins_example() {
+        let input = include_bytes!("../../inputs/example/day09.txt");
+        let labels = basins(&parse(input), width(input));
+
+        let mut sizes = (0..4)
+            .map(|basin| labels.iter().filter(|&&l| l == Some(basin)).count())
+            .collect::<Vec<_>>();
+        sizes.sort_unstable();
+
+        assert_eq!(sizes, vec![3, 9, 9, 14]);
+        assert_eq!(labels.iter().filter(|l| l.is_none()).count(), 15);
+    }
+
+    #[test]
+    fn ppm_example() {
+        let input = include_bytes!("../../inputs/example/day09.txt");
+        let mut buffer = Vec::new();
+        BasinMapFmt::new(input).write_ppm(&mut buffer, 2).unwrap();
+
+        let header = b"P6\n20 10\n255\n";
+        assert!(buffer.starts_with(header));
+        assert_eq!(buffer.len(), header.len() + 20 * 10 * 3);
+
+        // the top left corner is part of a basin, so it's not drawn as a ridge
+        assert_ne!(buffer[header.len()..header.len() + 3], [40, 40, 40]);
+    }
+
+    #[test]
+    fn render_marks_low_points() {
+        let input = include_bytes!("../../inputs/example/day09.txt");
+        let rendered = BasinMapFmt::new(input).to_string();
+
+        assert_eq!(rendered.lines().count(), 5);
+        assert_eq!(rendered.matches("\x1b[1;7m").count(), 4);
+    }
+}
+
 #[cfg(test)]
 mod tests_part2 {
     use crate::part2;
@@ -1,131 +1,407 @@
+use anyhow::{anyhow, bail, Context};
 use comfy_table::{Cell, Row, Table};
 use std::collections::HashMap;
-use std::fmt::{Debug, Formatter};
+use std::fmt::Formatter;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
 
 fn main() -> anyhow::Result<()> {
     let contents = include_str!("../../inputs/day11.txt");
-    let part1 = part1(contents);
+
+    // Optionally, change the rules: --threshold <energy>, --topology <bounded|toroidal> and
+    // --neighbourhood <moore|von-neumann|custom:<row>,<col>/...>, count the flashes after any
+    // amount of steps (--flashes-after <steps>), or show the octopuses in action, either in the
+    // terminal (--animate <steps>), or as a series of images (--pgm <steps> <dir>)
+    let mut args = std::env::args().skip(1);
+    let mut rules = Rules::default();
+    let mut flashes_after = None;
+    let mut delay = Duration::from_millis(100);
+    let mut animate = None;
+    let mut pgm = None;
+
+    let number = |arg: Option<String>| -> anyhow::Result<u64> {
+        let arg = arg.with_context(|| anyhow!("Expected a number"))?;
+        arg.parse()
+            .with_context(|| anyhow!("Unable to parse '{}' as a number", arg))
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--threshold" => {
+                let threshold = number(args.next())?;
+                rules.threshold = u8::try_from(threshold)
+                    .with_context(|| anyhow!("The flash threshold {} is too large", threshold))?;
+            }
+            "--topology" => {
+                rules.topology = args
+                    .next()
+                    .with_context(|| anyhow!("Expected a topology"))?
+                    .parse()?
+            }
+            "--neighbourhood" => {
+                rules.neighbourhood = args
+                    .next()
+                    .with_context(|| anyhow!("Expected a neighbourhood"))?
+                    .parse()?
+            }
+            "--flashes-after" => flashes_after = Some(number(args.next())?),
+            "--animate" => animate = Some(number(args.next())? as usize),
+            "--delay" => delay = Duration::from_millis(number(args.next())?),
+            "--pgm" => {
+                let steps = number(args.next())? as usize;
+                let dir = args
+                    .next()
+                    .with_context(|| anyhow!("Expected a directory after the amount of steps"))?;
+                pgm = Some((steps, dir));
+            }
+            _ => bail!("Unknown argument '{}'", arg),
+        }
+    }
+
+    let grid = Grid::from_str(contents).with_rules(rules)?;
+    let part1 = part1(grid.clone());
 
     println!("(day 11) part 1: {}", part1);
 
-    let part2 = part2(contents);
-    println!("(day 11) part 2: {}", part2);
+    match part2(grid.clone()) {
+        Some(part2) => println!("(day 11) part 2: {}", part2),
+        None => println!("(day 11) part 2: the octopuses never flash simultaneously"),
+    }
 
-    Ok(())
-}
+    if let Some(steps) = flashes_after {
+        let flashes = Cycle::detect(grid.clone()).flashes_after(steps);
+        println!("(day 11) flashes after {} steps: {}", steps, flashes);
+    }
 
-fn part1(input: &str) -> usize {
-    let mut grid = Grid::from_str(input);
+    if let Some(steps) = animate {
+        let animation = Animation::record(grid.clone(), steps);
+        let stdout = std::io::stdout();
+        animation.play(&mut stdout.lock(), delay)?;
+    }
 
-    (0..100)
-        .map(|_| {
-            grid.step();
-            grid.count_flashes()
-        })
-        .sum()
+    if let Some((steps, dir)) = pgm {
+        let animation = Animation::record(grid, steps);
+        animation.save_pgm(Path::new(&dir))?;
+    }
+
+    Ok(())
 }
 
-fn part2(input: &str) -> usize {
-    let mut grid = Grid::from_str(input);
+fn part1(mut grid: Grid) -> usize {
+    (0..100).map(|_| grid.step()).sum()
+}
 
-    std::iter::repeat(())
-        .enumerate()
-        .find(|_| {
-            grid.step();
-            grid.flashed_simultaneously()
-        })
-        .map(|(i, _)| i + 1)
-        .unwrap()
+/// The first step during which all octopuses flash, or `None` if that never happens
+fn part2(grid: Grid) -> Option<usize> {
+    Cycle::detect(grid).first_synchronization()
 }
 
+#[derive(Clone)]
 struct Grid {
-    map: HashMap<(usize, usize), Octopus>,
+    // energy levels, stored row major
+    energy: Vec<u8>,
+    width: usize,
+    height: usize,
+    rules: Rules,
 }
 
 impl Grid {
     fn from_str(input: &str) -> Self {
-        let map = input
+        let energy = input
             .lines()
-            .enumerate()
-            .flat_map(|(i, line)| {
-                line.as_bytes()
-                    .iter()
-                    .enumerate()
-                    .map(|(j, b)| ((i, j), Octopus::new(*b - b'0')))
-                    .collect::<Vec<((usize, usize), Octopus)>>()
-            })
-            .collect::<HashMap<(usize, usize), Octopus>>();
+            .flat_map(|line| line.as_bytes().iter().map(|b| *b - b'0'))
+            .collect::<Vec<u8>>();
 
-        Self { map }
+        let width = input.lines().next().map(str::len).unwrap_or(0);
+        let height = energy.len().checked_div(width).unwrap_or(0);
+
+        Self {
+            energy,
+            width,
+            height,
+            rules: Rules::default(),
+        }
     }
 
-    fn step(&mut self) {
+    fn with_rules(self, rules: Rules) -> anyhow::Result<Self> {
+        // an octopus must be able to gain energy before flashing, and to go over the threshold
+        // without overflowing
+        if rules.threshold == 0 || rules.threshold == u8::MAX {
+            bail!(
+                "The flash threshold must be between 1 and {}, but was {}",
+                u8::MAX - 1,
+                rules.threshold
+            );
+        }
+
+        Ok(Self { rules, ..self })
+    }
+
+    /// Advances the grid by one step, and returns the amount of octopuses which flashed
+    fn step(&mut self) -> usize {
+        let threshold = self.rules.threshold;
+        let mut worklist = Vec::new();
+
         // First, the energy level of each octopus increases by 1.
-        self.map
-            .values_mut()
-            .for_each(|octopus| octopus.raise_energy_level());
+        // Then, any octopus with an energy level greater than 9 (the threshold) flashes
+        for (index, energy) in self.energy.iter_mut().enumerate() {
+            *energy += 1;
 
-        // Then, any octopus with an energy level greater than 9 flashes
-        while let Some((&center, octopus)) = self.needs_update() {
-            // This increases the energy level of all adjacent octopuses by 1,
-            octopus.flash();
+            if *energy > threshold {
+                worklist.push(index);
+            }
+        }
 
-            // If this causes an octopus to have an energy level greater than 9, it also flashes
-            self.increase_neighbour_energy_levels(center);
+        let mut flashes = 0;
+
+        while let Some(center) = worklist.pop() {
+            flashes += 1;
+
+            // This increases the energy level of all adjacent octopuses by 1,
+            // If this causes an octopus to have an energy level greater than 9, it also flashes.
+            // An octopus can flash at most once per step, so we only enqueue an octopus at the
+            // moment its energy level crosses the threshold.
+            for neighbour in self.rules.neighbours(center, self.width, self.height) {
+                let energy = &mut self.energy[neighbour];
+                let before = *energy;
+                *energy = energy.saturating_add(1);
+
+                if before == threshold {
+                    worklist.push(neighbour);
+                }
+            }
         }
 
-        self.reset();
-    }
+        // Finally, any octopus that flashed during this step has its energy level set to 0
+        self.energy
+            .iter_mut()
+            .filter(|energy| **energy > threshold)
+            .for_each(|energy| *energy = 0);
 
-    fn needs_update(&mut self) -> Option<(&(usize, usize), &mut Octopus)> {
-        self.map.iter_mut().find(|(_k, v)| v.should_flash())
+        flashes
     }
+}
 
-    fn increase_neighbour_energy_levels(&mut self, center: (usize, usize)) {
-        for i in -1..=1 {
-            for j in -1..=1 {
-                let xx = ((center.0 as isize) + i) as usize;
-                let yy = ((center.1 as isize) + j) as usize;
+/// The simulation is deterministic, and a grid has finitely many states, so eventually it must
+/// revisit a state, after which it repeats itself forever. Knowing where this cycle starts and
+/// how long it is, we can answer questions about any step, without simulating up to that step.
+#[derive(Debug)]
+struct Cycle {
+    // the amount of flashes during each step, up to and including the first repeated state
+    flashes: Vec<usize>,
+    // the amount of octopuses in the grid
+    size: usize,
+    // the amount of steps before entering the cycle
+    start: usize,
+    // the amount of steps after which the cycle repeats
+    period: usize,
+}
 
-                if let Some(neighbour) = self.map.get_mut(&(xx, yy)) {
-                    neighbour.raise_energy_level();
-                }
+impl Cycle {
+    fn detect(mut grid: Grid) -> Self {
+        let size = grid.energy.len();
+        let mut seen = HashMap::new();
+        let mut flashes = Vec::new();
+
+        seen.insert(grid.energy.clone(), 0_usize);
+
+        loop {
+            flashes.push(grid.step());
+            let step = flashes.len();
+
+            if let Some(start) = seen.insert(grid.energy.clone(), step) {
+                return Self {
+                    flashes,
+                    size,
+                    start,
+                    period: step - start,
+                };
             }
         }
     }
 
-    fn reset(&mut self) {
-        self.map
-            .iter_mut()
-            .for_each(|(_, octopus)| octopus.reset_did_flash());
+    /// The first (1-based) step during which every octopus flashed; if it didn't happen before
+    /// the cycle closed, it never will
+    fn first_synchronization(&self) -> Option<usize> {
+        self.flashes
+            .iter()
+            .position(|&flashes| flashes == self.size)
+            .map(|i| i + 1)
     }
 
-    /// Requires generation to be complete
-    fn count_flashes(&self) -> usize {
-        self.map
+    /// The total amount of flashes during the first `steps` steps
+    fn flashes_after(&self, steps: u64) -> u128 {
+        let sum = |steps: &[usize]| steps.iter().map(|&f| f as u128).sum::<u128>();
+
+        if steps <= self.flashes.len() as u64 {
+            return sum(&self.flashes[..steps as usize]);
+        }
+
+        let cycle = &self.flashes[self.start..self.start + self.period];
+        let remaining = steps - self.start as u64;
+        let repetitions = u128::from(remaining / self.period as u64);
+        let tail = (remaining % self.period as u64) as usize;
+
+        sum(&self.flashes[..self.start]) + repetitions * sum(cycle) + sum(&cycle[..tail])
+    }
+}
+
+/// The rules by which octopuses flash and affect each other. The defaults are those of the
+/// puzzle: an octopus flashes when its energy level exceeds 9, and affects its 8 neighbours,
+/// on a grid which doesn't wrap around.
+#[derive(Debug, Clone)]
+struct Rules {
+    threshold: u8,
+    topology: Topology,
+    neighbourhood: Neighbourhood,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            threshold: 9,
+            topology: Topology::Bounded,
+            neighbourhood: Neighbourhood::Moore,
+        }
+    }
+}
+
+impl Rules {
+    /// The indices of the octopuses which are affected when the octopus at `center` flashes
+    fn neighbours(
+        &self,
+        center: usize,
+        width: usize,
+        height: usize,
+    ) -> impl Iterator<Item = usize> + '_ {
+        let (row, col) = ((center / width) as isize, (center % width) as isize);
+        let (width, height) = (width as isize, height as isize);
+        let topology = self.topology;
+
+        self.neighbourhood
+            .offsets()
             .iter()
-            .filter(|(_, octopus)| octopus.energy_level == 0)
-            .count()
+            .filter_map(move |&(i, j)| {
+                let (row, col) = (row + i, col + j);
+
+                let (row, col) = match topology {
+                    // Neighbours beyond the edge simply don't exist
+                    Topology::Bounded => {
+                        if !(0..height).contains(&row) || !(0..width).contains(&col) {
+                            return None;
+                        }
+
+                        (row, col)
+                    }
+                    // Neighbours beyond the edge are found at the opposite edge
+                    Topology::Toroidal => (row.rem_euclid(height), col.rem_euclid(width)),
+                };
+
+                Some((row * width + col) as usize)
+            })
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Topology {
+    Bounded,
+    Toroidal,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Neighbourhood {
+    /// The 8 surrounding cells, including diagonals
+    Moore,
+    /// The 4 orthogonally adjacent cells
+    VonNeumann,
+    /// Arbitrary (row, column) offsets relative to the flashing octopus
+    Custom(Vec<(isize, isize)>),
+}
+
+impl FromStr for Topology {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bounded" => Ok(Self::Bounded),
+            "toroidal" => Ok(Self::Toroidal),
+            _ => bail!(
+                "Unknown topology '{}', expected one of: bounded, toroidal",
+                s
+            ),
+        }
     }
+}
 
-    fn flashed_simultaneously(&self) -> bool {
-        self.map.len() == self.count_flashes()
+impl FromStr for Neighbourhood {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let offset = |offset: &str| -> anyhow::Result<(isize, isize)> {
+            let parse = |n: &str| {
+                n.parse::<isize>()
+                    .with_context(|| anyhow!("Unable to parse '{}' as an offset", n))
+            };
+
+            let (row, col) = offset
+                .split_once(',')
+                .with_context(|| anyhow!("Expected an offset like -1,0, but got '{}'", offset))?;
+
+            Ok((parse(row)?, parse(col)?))
+        };
+
+        match s {
+            "moore" => Ok(Self::Moore),
+            "von-neumann" => Ok(Self::VonNeumann),
+            _ => match s.strip_prefix("custom:") {
+                Some(offsets) => Ok(Self::Custom(
+                    offsets
+                        .split('/')
+                        .map(offset)
+                        .collect::<anyhow::Result<_>>()?,
+                )),
+                None => bail!(
+                    "Unknown neighbourhood '{}', expected moore, von-neumann or custom offsets",
+                    s
+                ),
+            },
+        }
+    }
+}
+
+impl Neighbourhood {
+    fn offsets(&self) -> &[(isize, isize)] {
+        const MOORE: [(isize, isize); 8] = [
+            (-1, -1),
+            (-1, 0),
+            (-1, 1),
+            (0, -1),
+            (0, 1),
+            (1, -1),
+            (1, 0),
+            (1, 1),
+        ];
+        const VON_NEUMANN: [(isize, isize); 4] = [(-1, 0), (0, -1), (0, 1), (1, 0)];
+
+        match self {
+            Neighbourhood::Moore => &MOORE,
+            Neighbourhood::VonNeumann => &VON_NEUMANN,
+            Neighbourhood::Custom(offsets) => offsets,
+        }
     }
 }
 
 impl std::fmt::Display for Grid {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let ii = self.map.keys().map(|(l, _r)| *l).max().unwrap_or(0);
-
-        let jj = self.map.keys().map(|(_l, r)| *r).max().unwrap_or(0);
-
         let mut table = Table::new();
 
-        for i in 0_usize..=ii {
+        for cells in self.energy.chunks(self.width) {
             let mut row = Row::new();
 
-            for j in 0_usize..=jj {
-                row.add_cell(Cell::new(format!("{}", self.map.get(&(i, j)).unwrap())));
+            for energy in cells {
+                row.add_cell(Cell::new(format!("{}", energy)));
             }
             table.add_row(row);
         }
@@ -134,76 +410,351 @@ impl std::fmt::Display for Grid {
     }
 }
 
-#[derive(Debug)]
-struct Octopus {
-    energy_level: u8,
-    did_flash: bool,
+// Not necessary for the solution, but fun to watch the octopuses synchronize
+struct Animation {
+    // the grid before the first step, followed by the grid after each step
+    frames: Vec<Vec<u8>>,
+    width: usize,
+    threshold: u8,
 }
 
-impl Octopus {
-    fn new(energy_level: u8) -> Self {
-        Self {
-            energy_level,
-            did_flash: false,
+impl Animation {
+    fn record(mut grid: Grid, steps: usize) -> Self {
+        let mut frames = Vec::with_capacity(steps + 1);
+        frames.push(grid.energy.clone());
+
+        for _ in 0..steps {
+            grid.step();
+            frames.push(grid.energy.clone());
         }
-    }
 
-    /// Raises the energy level by one
-    fn raise_energy_level(&mut self) {
-        if !self.did_flash {
-            self.energy_level += 1;
+        Self {
+            frames,
+            width: grid.width,
+            threshold: grid.rules.threshold,
         }
     }
 
-    fn should_flash(&self) -> bool {
-        self.energy_level > 9 && !self.did_flash
+    /// Every octopus gains energy during a step, so after a step, only the octopuses which
+    /// flashed have an energy level of 0. Nothing has flashed yet in the first frame.
+    fn flashed(&self, frame: usize, index: usize) -> bool {
+        frame > 0 && self.frames[frame][index] == 0
     }
 
-    fn flash(&mut self) {
-        self.energy_level = 0;
-        self.did_flash = true;
+    /// Plays the animation in a terminal, by redrawing the screen for each frame
+    fn play<W: Write>(&self, output: &mut W, delay: Duration) -> anyhow::Result<()> {
+        for (step, frame) in self.frames.iter().enumerate() {
+            // clear the screen, and move the cursor to the top left
+            let mut screen = format!("\x1b[2J\x1b[H(day 11) step {}\n", step);
+
+            for (i, cells) in frame.chunks(self.width).enumerate() {
+                for (j, energy) in cells.iter().enumerate() {
+                    if self.flashed(step, i * self.width + j) {
+                        screen.push_str(&format!("\x1b[1;93m{}\x1b[0m", energy));
+                    } else {
+                        screen.push_str(&format!("\x1b[2m{}\x1b[0m", energy));
+                    }
+                }
+                screen.push('\n');
+            }
+
+            output
+                .write_all(screen.as_bytes())
+                .and_then(|_| output.flush())
+                .with_context(|| anyhow!("Unable to write frame {}", step))?;
+
+            std::thread::sleep(delay);
+        }
+
+        Ok(())
     }
 
-    fn reset_did_flash(&mut self) {
-        self.did_flash = false;
+    /// Writes a single frame as a binary PGM (P5) image, one pixel per octopus. Flashing
+    /// octopuses are white, the others are darker grey the less energy they have.
+    fn write_pgm<W: Write>(&self, frame: usize, output: &mut W) -> anyhow::Result<()> {
+        let cells = &self.frames[frame];
+        let height = cells.len() / self.width;
+
+        let pixels = cells
+            .iter()
+            .enumerate()
+            .map(|(index, &energy)| {
+                if self.flashed(frame, index) {
+                    255
+                } else {
+                    // Never divide by zero, even though the rules don't allow such a threshold.
+                    // The input itself may have energy levels above the threshold.
+                    let threshold = u32::from(self.threshold.max(1));
+                    (u32::from(energy) * 160 / threshold).min(160) as u8
+                }
+            })
+            .collect::<Vec<u8>>();
+
+        write!(output, "P5\n{} {}\n255\n", self.width, height)
+            .and_then(|_| output.write_all(&pixels))
+            .and_then(|_| output.flush())
+            .with_context(|| anyhow!("Unable to write frame {} as PGM", frame))
     }
-}
 
-impl std::fmt::Display for Octopus {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("{}", self.energy_level))
+    /// Saves each frame as a numbered PGM image (`frame_0000.pgm`, `frame_0001.pgm`, ...)
+    fn save_pgm(&self, dir: &Path) -> anyhow::Result<()> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| anyhow!("Unable to create directory '{}'", dir.display()))?;
+
+        for frame in 0..self.frames.len() {
+            let path = dir.join(format!("frame_{:04}.pgm", frame));
+            let file = std::fs::File::create(&path)
+                .with_context(|| anyhow!("Unable to create '{}'", path.display()))?;
+
+            self.write_pgm(frame, &mut std::io::BufWriter::new(file))?;
+        }
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{part1, part2};
+    use crate::{part1, part2, Animation, Cycle, Grid, Neighbourhood, Rules, Topology};
+    use std::time::Duration;
 
     #[test]
     fn part1_example() {
         let input = include_str!("../../inputs/example/day11.txt");
 
-        assert_eq!(part1(input), 1656);
+        assert_eq!(part1(Grid::from_str(input)), 1656);
     }
 
     #[test]
     fn part1_solution() {
         let input = include_str!("../../inputs/day11.txt");
 
-        assert_eq!(part1(input), 1735);
+        assert_eq!(part1(Grid::from_str(input)), 1735);
     }
 
     #[test]
     fn part2_example() {
         let input = include_str!("../../inputs/example/day11.txt");
 
-        assert_eq!(part2(input), 195);
+        assert_eq!(part2(Grid::from_str(input)), Some(195));
     }
 
     #[test]
     fn part2_solution() {
         let input = include_str!("../../inputs/day11.txt");
 
-        assert_eq!(part2(input), 400);
+        assert_eq!(part2(Grid::from_str(input)), Some(400));
+    }
+
+    #[test]
+    fn large_grid() {
+        let example = include_str!("../../inputs/example/day11.txt");
+
+        // tile the example 10 times in each direction, to a 100x100 grid
+        let row = |line: &str| line.repeat(10);
+        let tile = example.lines().map(row).collect::<Vec<_>>().join("\n");
+        let input = vec![tile; 10].join("\n");
+
+        let mut grid = Grid::from_str(&input);
+        assert_eq!((grid.width, grid.height), (100, 100));
+
+        let flashes = (0..2000).map(|_| grid.step()).sum::<usize>();
+
+        assert!(flashes > 0);
+        assert!(grid.energy.iter().all(|&energy| energy <= 9));
+    }
+
+    #[test]
+    fn small_example() {
+        let input = "11111\n19991\n19191\n19991\n11111";
+        let mut grid = Grid::from_str(input);
+
+        assert_eq!(grid.step(), 9);
+        assert_eq!(
+            grid.energy,
+            vec![3, 4, 5, 4, 3, 4, 0, 0, 0, 4, 5, 0, 0, 0, 5, 4, 0, 0, 0, 4, 3, 4, 5, 4, 3]
+        );
+
+        assert_eq!(grid.step(), 0);
+        assert_eq!(
+            grid.energy,
+            vec![4, 5, 6, 5, 4, 5, 1, 1, 1, 5, 6, 1, 1, 1, 6, 5, 1, 1, 1, 5, 4, 5, 6, 5, 4]
+        );
+    }
+
+    #[test]
+    fn bounded_corner() {
+        let mut grid = Grid::from_str("900\n000\n000");
+
+        assert_eq!(grid.step(), 1);
+        assert_eq!(grid.energy, vec![0, 2, 1, 2, 2, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn toroidal_corner() {
+        let rules = Rules {
+            topology: Topology::Toroidal,
+            ..Rules::default()
+        };
+        let mut grid = Grid::from_str("900\n000\n000").with_rules(rules).unwrap();
+
+        assert_eq!(grid.step(), 1);
+        assert_eq!(grid.energy, vec![0, 2, 2, 2, 2, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn von_neumann_neighbourhood() {
+        let rules = Rules {
+            neighbourhood: Neighbourhood::VonNeumann,
+            ..Rules::default()
+        };
+        let mut grid = Grid::from_str("000\n090\n000").with_rules(rules).unwrap();
+
+        assert_eq!(grid.step(), 1);
+        assert_eq!(grid.energy, vec![1, 2, 1, 2, 0, 2, 1, 2, 1]);
+    }
+
+    #[test]
+    fn custom_neighbourhood_beyond_edge() {
+        let rules = Rules {
+            neighbourhood: Neighbourhood::Custom(vec![(0, 2), (0, -2)]),
+            ..Rules::default()
+        };
+        let mut grid = Grid::from_str("9000").with_rules(rules).unwrap();
+
+        assert_eq!(grid.step(), 1);
+        assert_eq!(grid.energy, vec![0, 1, 2, 1]);
+    }
+
+    #[test]
+    fn parse_rules() {
+        assert_eq!("toroidal".parse::<Topology>().unwrap(), Topology::Toroidal);
+        assert!("spherical".parse::<Topology>().is_err());
+
+        assert_eq!(
+            "von-neumann".parse::<Neighbourhood>().unwrap(),
+            Neighbourhood::VonNeumann
+        );
+        assert_eq!(
+            "custom:0,2/-1,0".parse::<Neighbourhood>().unwrap(),
+            Neighbourhood::Custom(vec![(0, 2), (-1, 0)])
+        );
+        assert!("custom:0".parse::<Neighbourhood>().is_err());
+        assert!("hexagonal".parse::<Neighbourhood>().is_err());
+    }
+
+    #[test]
+    fn invalid_threshold() {
+        for threshold in [0, u8::MAX] {
+            let rules = Rules {
+                threshold,
+                ..Rules::default()
+            };
+
+            assert!(Grid::from_str("302").with_rules(rules).is_err());
+        }
+    }
+
+    #[test]
+    fn custom_threshold() {
+        let rules = Rules {
+            threshold: 3,
+            ..Rules::default()
+        };
+        let mut grid = Grid::from_str("302").with_rules(rules).unwrap();
+
+        assert_eq!(grid.step(), 1);
+        assert_eq!(grid.energy, vec![0, 2, 3]);
+
+        // the last octopus reaches 4, and flashes, which in turn pushes the middle one over the edge
+        assert_eq!(grid.step(), 2);
+        assert_eq!(grid.energy, vec![2, 0, 0]);
+    }
+
+    #[test]
+    fn never_synchronizes() {
+        let cycle = Cycle::detect(Grid::from_str("013"));
+
+        assert_eq!(cycle.first_synchronization(), None);
+        assert_eq!((cycle.start, cycle.period), (16, 9));
+    }
+
+    #[test]
+    fn flashes_after_matches_simulation() {
+        let example = include_str!("../../inputs/example/day11.txt");
+
+        for input in [example, "013"] {
+            let cycle = Cycle::detect(Grid::from_str(input));
+            let mut grid = Grid::from_str(input);
+            let mut total = 0;
+
+            for steps in 0..500 {
+                assert_eq!(cycle.flashes_after(steps), total, "{} steps", steps);
+                total += grid.step() as u128;
+            }
+        }
+    }
+
+    #[test]
+    fn flashes_after_many_steps() {
+        let input = include_str!("../../inputs/example/day11.txt");
+        let cycle = Cycle::detect(Grid::from_str(input));
+
+        // once synchronized, all 100 octopuses flash together every 10 steps
+        let synchronized = cycle.flashes_after(195);
+        let steps = 1_000_000_000_000;
+
+        assert_eq!(
+            cycle.flashes_after(steps),
+            synchronized + 100 * u128::from((steps - 195) / 10)
+        );
+    }
+
+    #[test]
+    fn animation_frames() {
+        let input = include_str!("../../inputs/example/day11.txt");
+        let animation = Animation::record(Grid::from_str(input), 195);
+
+        assert_eq!(animation.frames.len(), 196);
+        assert!((0..100).all(|index| animation.flashed(195, index)));
+        assert!((0..100).all(|index| !animation.flashed(0, index)));
+    }
+
+    #[test]
+    fn animation_play() {
+        let animation = Animation::record(Grid::from_str("900\n000"), 2);
+
+        let mut buffer = Vec::new();
+        animation.play(&mut buffer, Duration::ZERO).unwrap();
+        let screen = String::from_utf8(buffer).unwrap();
+
+        assert_eq!(screen.matches("\x1b[2J").count(), 3);
+        // only the corner octopus flashes, during the first step
+        assert_eq!(screen.matches("\x1b[1;93m").count(), 1);
+    }
+
+    #[test]
+    fn animation_pgm() {
+        let animation = Animation::record(Grid::from_str("900\n000"), 1);
+
+        let mut buffer = Vec::new();
+        animation.write_pgm(1, &mut buffer).unwrap();
+
+        let header = b"P5\n3 2\n255\n";
+        assert_eq!(&buffer[..header.len()], header);
+        assert_eq!(&buffer[header.len()..], &[255, 35, 17, 35, 35, 17]);
+    }
+
+    #[test]
+    fn animation_pgm_extreme_thresholds() {
+        let animation = Animation {
+            frames: vec![vec![0, 1, 9, 200]],
+            width: 2,
+            threshold: 0,
+        };
+
+        let mut buffer = Vec::new();
+        animation.write_pgm(0, &mut buffer).unwrap();
+        assert_eq!(&buffer[buffer.len() - 4..], &[0, 160, 160, 160]);
     }
 }
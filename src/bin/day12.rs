@@ -1,77 +1,682 @@
-use std::collections::HashMap;
+use anyhow::{anyhow, bail, Context};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::hash::Hash;
 
 fn main() -> anyhow::Result<()> {
     let contents = include_str!("../../inputs/day12.txt");
     let graph = Graph::from_str(contents);
 
-    let part1 = part1(&graph);
+    // Optionally, use other endpoints than 'start' and 'end': --start <cave> --end <cave>, or
+    // count the paths of another policy: --policy <once|twice|up-to:N>, or with a quota of
+    // visits per cave: --quota <cave> <visits>, which may be given more than once.
+    // List the paths themselves: --paths <1|2> [--through <cave>] [--max-len <caves>]
+    // [--limit <paths>], or draw the graph in DOT format: --dot <file>, which highlights the
+    // first of these paths, if given
+    let mut args = std::env::args().skip(1);
+    let (mut start, mut end) = (None, None);
+    let mut policy = None;
+    let mut quotas = Vec::new();
+    let mut dot = None;
+    let mut part = None;
+    let mut through = None;
+    let mut max_len = None;
+    let mut limit = None;
+
+    let number = |arg: Option<String>| -> anyhow::Result<usize> {
+        let arg = arg.with_context(|| anyhow!("Expected a number"))?;
+        arg.parse()
+            .with_context(|| anyhow!("Unable to parse '{}' as a number", arg))
+    };
+
+    let cave = |arg: Option<String>, flag: &str| -> anyhow::Result<String> {
+        let cave = arg.with_context(|| anyhow!("Expected a cave after '{}'", flag))?;
+
+        if !graph.ids.contains_key(cave.as_str()) {
+            bail!("No such cave '{}'", cave);
+        }
+
+        Ok(cave)
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--start" => start = Some(cave(args.next(), "--start")?),
+            "--end" => end = Some(cave(args.next(), "--end")?),
+            "--policy" => {
+                let arg = args
+                    .next()
+                    .with_context(|| anyhow!("Expected a policy after '--policy'"))?;
+
+                policy = Some(match arg.as_str() {
+                    "once" => OneSmallCaveUpTo(1),
+                    "twice" => OneSmallCaveUpTo(2),
+                    up_to => match up_to.strip_prefix("up-to:") {
+                        Some(times) => match number(Some(times.to_string()))? {
+                            0 => bail!("A small cave must be visitable at least once"),
+                            times => OneSmallCaveUpTo(times),
+                        },
+                        None => bail!("Unknown policy '{}', expected once, twice or up-to:N", arg),
+                    },
+                });
+            }
+            "--quota" => quotas.push((cave(args.next(), "--quota")?, number(args.next())?)),
+            "--paths" => part = Some(number(args.next())?),
+            "--through" => through = Some(cave(args.next(), "--through")?),
+            "--max-len" => max_len = Some(number(args.next())?),
+            "--limit" => limit = Some(number(args.next())?),
+            "--dot" => {
+                let file = args
+                    .next()
+                    .with_context(|| anyhow!("Expected a file after '--dot'"))?;
+                dot = Some(file);
+            }
+            _ => bail!("Unknown argument '{}'", arg),
+        }
+    }
+
+    let graph = match (start.as_deref(), end.as_deref()) {
+        (None, None) => graph,
+        (start, end) => graph.with_endpoints(start.unwrap_or("start"), end.unwrap_or("end"))?,
+    };
+
+    let part1 = part1(&graph)?;
 
     println!("(day 12) part 1: {}", part1);
 
-    let part2 = part2(&graph);
+    let part2 = part2(&graph)?;
     println!("(day 12) part 2: {}", part2);
 
+    if let Some(policy) = policy {
+        println!(
+            "(day 12) paths visiting a single small cave up to {} times: {}",
+            policy.0,
+            graph.count_paths(&policy)?
+        );
+    }
+
+    if !quotas.is_empty() {
+        let policy = quotas.iter().fold(Quotas::new(), |policy, (cave, visits)| {
+            policy.quota(cave, *visits)
+        });
+
+        println!(
+            "(day 12) paths within the quotas: {}",
+            graph.count_paths(&policy)?
+        );
+    }
+
+    let through = through.as_deref();
+
+    let mut paths: Box<dyn Iterator<Item = Vec<&str>>> = match part {
+        None => Box::new(std::iter::empty()),
+        Some(1) => Box::new(filter_paths(
+            graph.paths(&SmallCavesOnce)?,
+            through,
+            max_len,
+            limit,
+        )),
+        Some(2) => Box::new(filter_paths(
+            graph.paths(&OneSmallCaveTwice)?,
+            through,
+            max_len,
+            limit,
+        )),
+        Some(part) => bail!(
+            "Expected the part (1 or 2) after '--paths', but got {}",
+            part
+        ),
+    };
+
+    if let Some(file) = dot {
+        let path = paths.next();
+        let dot = DotFmt::new(&graph, path.as_deref()).to_string();
+
+        std::fs::write(&file, dot).with_context(|| anyhow!("Unable to write '{}'", file))?;
+    } else {
+        for path in paths {
+            println!("{}", path.join(","));
+        }
+    }
+
     Ok(())
 }
 
-fn part1(graph: &Graph) -> usize {
-    let cap = graph.vertices.len() * 2;
+fn filter_paths<'g, 's, P: Policy>(
+    paths: Paths<'g, 's, P>,
+    through: Option<&str>,
+    max_len: Option<usize>,
+    limit: Option<usize>,
+) -> Paths<'g, 's, P> {
+    let paths = match through {
+        Some(cave) => paths.through(cave),
+        None => paths,
+    };
 
-    graph.dfs(Vec::with_capacity(cap), "start", true)
+    let paths = match max_len {
+        Some(max_len) => paths.max_len(max_len),
+        None => paths,
+    };
+
+    match limit {
+        Some(limit) => paths.limit(limit),
+        None => paths,
+    }
 }
 
-fn part2(graph: &Graph) -> usize {
-    let cap = graph.vertices.len() * 2;
+fn part1(graph: &Graph) -> anyhow::Result<usize> {
+    graph.count_paths(&SmallCavesOnce)
+}
 
-    graph.dfs(Vec::with_capacity(cap), "start", false)
+fn part2(graph: &Graph) -> anyhow::Result<usize> {
+    graph.count_paths(&OneSmallCaveTwice)
 }
 
-type Map<'s> = HashMap<&'s str, Vec<&'s str>>;
+/// Caves are interned to ids, which index into the vectors of the graph
+type CaveId = usize;
+
+/// The set of visited small caves, one bit per small cave
+type Visited = u64;
 
 struct Graph<'s> {
-    vertices: Map<'s>,
+    names: Vec<&'s str>,
+    ids: HashMap<&'s str, CaveId>,
+    neighbours: Vec<Vec<CaveId>>,
+    // for each small cave, the bit which represents it in a `Visited` set
+    small: Vec<Option<Visited>>,
+    // the endpoints of each path, if the graph has such caves
+    start: Option<CaveId>,
+    end: Option<CaveId>,
 }
 
 impl<'s> Graph<'s> {
     fn from_str(input: &'s str) -> Self {
-        let mut map = Map::new();
+        let mut graph = Self {
+            names: Vec::new(),
+            ids: HashMap::new(),
+            neighbours: Vec::new(),
+            small: Vec::new(),
+            start: None,
+            end: None,
+        };
 
         input.lines().for_each(|c| {
             let (s, t) = c.split_once('-').unwrap();
-            map.entry(s).or_default().push(t);
-            map.entry(t).or_default().push(s);
+            let (s, t) = (graph.intern(s), graph.intern(t));
+            graph.neighbours[s].push(t);
+            graph.neighbours[t].push(s);
+        });
+
+        graph.start = graph.ids.get("start").copied();
+        graph.end = graph.ids.get("end").copied();
+
+        graph
+    }
+
+    /// Uses other caves than 'start' and 'end' as the endpoints of each path
+    fn with_endpoints(self, start: &str, end: &str) -> anyhow::Result<Self> {
+        let find = |name: &str| {
+            self.ids
+                .get(name)
+                .copied()
+                .with_context(|| anyhow!("No such cave '{}'", name))
+        };
+
+        let (start, end) = (find(start)?, find(end)?);
+
+        if start == end {
+            bail!("The start and end of a path must be different caves");
+        }
+
+        Ok(Self {
+            start: Some(start),
+            end: Some(end),
+            ..self
+        })
+    }
+
+    fn endpoints(&self) -> anyhow::Result<(CaveId, CaveId)> {
+        let start = self
+            .start
+            .with_context(|| anyhow!("No start cave, expected a cave named 'start'"))?;
+        let end = self
+            .end
+            .with_context(|| anyhow!("No end cave, expected a cave named 'end'"))?;
+
+        Ok((start, end))
+    }
+
+    fn intern(&mut self, name: &'s str) -> CaveId {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+
+        let id = self.names.len();
+        let small_caves = self.small.iter().flatten().count();
+
+        let bit = name.is_small().then(|| {
+            assert!(
+                small_caves < Visited::BITS as usize,
+                "At most {} small caves are supported",
+                Visited::BITS
+            );
+
+            1 << small_caves
         });
 
-        Self { vertices: map }
+        self.names.push(name);
+        self.ids.insert(name, id);
+        self.neighbours.push(Vec::new());
+        self.small.push(bit);
+
+        id
+    }
+
+    fn id(&self, name: &str) -> CaveId {
+        *self
+            .ids
+            .get(name)
+            .unwrap_or_else(|| panic!("No such cave '{}'", name))
+    }
+
+    /// Counts the paths from start to end which the given policy allows
+    fn count_paths<P: Policy>(&self, policy: &P) -> anyhow::Result<usize> {
+        let endpoints = self.endpoints()?;
+
+        // Memoized path counts, keyed by the current cave, and the policy state after entering it
+        let mut memo = HashMap::new();
+
+        Ok(self.count(
+            policy,
+            endpoints,
+            endpoints.0,
+            policy.initial(self),
+            &mut memo,
+        ))
+    }
+
+    fn count<P: Policy>(
+        &self,
+        policy: &P,
+        (start, end): (CaveId, CaveId),
+        current: CaveId,
+        state: P::State,
+        memo: &mut HashMap<(CaveId, P::State), usize>,
+    ) -> usize {
+        if let Some(&count) = memo.get(&(current, state.clone())) {
+            return count;
+        }
+
+        let count = self.neighbours[current]
+            .iter()
+            .map(|&cave| match cave {
+                // Reaching the end completes a path, and the start may only be visited once
+                cave if cave == end => 1,
+                cave if cave == start => 0,
+                cave => policy
+                    .enter(self, cave, &state)
+                    .map(|state| self.count(policy, (start, end), cave, state, memo))
+                    .unwrap_or(0),
+            })
+            .sum();
+
+        memo.insert((current, state), count);
+        count
+    }
+
+    /// Enumerates the paths from start to end which the given policy allows, as the names of
+    /// the caves along the path
+    fn paths<'g, P: Policy>(&'g self, policy: &'g P) -> anyhow::Result<Paths<'g, 's, P>> {
+        let (start, end) = self.endpoints()?;
+
+        Ok(Paths {
+            graph: self,
+            policy,
+            start,
+            end,
+            stack: vec![Frame {
+                cave: start,
+                next: 0,
+                state: policy.initial(self),
+            }],
+            through: None,
+            max_len: None,
+            limit: None,
+        })
+    }
+}
+
+/// Decides whether a cave may be entered, given the caves visited along the path so far.
+/// The start and end caves are not subject to policies: the start can't be revisited, and
+/// reaching the end completes the path.
+trait Policy {
+    /// What the policy remembers of the caves visited so far
+    type State: Clone + Eq + Hash;
+
+    /// The state of a path which only consists of the start cave
+    fn initial(&self, graph: &Graph<'_>) -> Self::State;
+
+    /// The state after entering `cave`, or `None` if it may not be entered
+    fn enter(&self, graph: &Graph<'_>, cave: CaveId, state: &Self::State) -> Option<Self::State>;
+}
+
+/// Part 1: small caves may be visited at most once
+struct SmallCavesOnce;
+
+impl Policy for SmallCavesOnce {
+    type State = Visited;
+
+    fn initial(&self, _graph: &Graph<'_>) -> Self::State {
+        0
+    }
+
+    fn enter(&self, graph: &Graph<'_>, cave: CaveId, &visited: &Visited) -> Option<Visited> {
+        match graph.small[cave] {
+            Some(bit) if visited & bit != 0 => None,
+            Some(bit) => Some(visited | bit),
+            None => Some(visited),
+        }
+    }
+}
+
+/// Part 2: a single small cave may be visited twice, the other small caves at most once
+struct OneSmallCaveTwice;
+
+impl Policy for OneSmallCaveTwice {
+    // the visited small caves, and whether one of them was visited twice
+    type State = (Visited, bool);
+
+    fn initial(&self, _graph: &Graph<'_>) -> Self::State {
+        (0, false)
+    }
+
+    fn enter(
+        &self,
+        graph: &Graph<'_>,
+        cave: CaveId,
+        &(visited, visited_twice): &Self::State,
+    ) -> Option<Self::State> {
+        match graph.small[cave] {
+            Some(bit) if visited & bit != 0 => (!visited_twice).then(|| (visited, true)),
+            Some(bit) => Some((visited | bit, visited_twice)),
+            None => Some((visited, visited_twice)),
+        }
+    }
+}
+
+/// A single small cave may be visited up to `k` times, the other small caves at most once
+struct OneSmallCaveUpTo(usize);
+
+impl Policy for OneSmallCaveUpTo {
+    // the visited small caves, and the small cave which was visited more than once, if any,
+    // together with how often it was visited
+    type State = (Visited, Option<(CaveId, usize)>);
+
+    fn initial(&self, _graph: &Graph<'_>) -> Self::State {
+        (0, None)
     }
 
-    fn dfs(&self, mut visited: Vec<&'s str>, current: &'s str, mut visited_twice: bool) -> usize {
-        if current == "end" {
-            return 1;
+    fn enter(
+        &self,
+        graph: &Graph<'_>,
+        cave: CaveId,
+        &(visited, repeated): &Self::State,
+    ) -> Option<Self::State> {
+        let bit = match graph.small[cave] {
+            Some(bit) => bit,
+            None => return Some((visited, repeated)),
+        };
+
+        if visited & bit == 0 {
+            return Some((visited | bit, repeated));
         }
 
-        if visited.contains(&current) {
-            if current == "start" {
-                return 0;
+        match repeated {
+            None if self.0 >= 2 => Some((visited, Some((cave, 2)))),
+            Some((repeated, times)) if repeated == cave && times < self.0 => {
+                Some((visited, Some((cave, times + 1))))
             }
+            _ => None,
+        }
+    }
+}
+
+/// Each cave may be visited as often as its quota allows. Unless specified otherwise, small
+/// caves have a quota of one visit, and big caves can be visited without limit.
+struct Quotas<'q> {
+    quotas: HashMap<&'q str, usize>,
+}
+
+impl<'q> Quotas<'q> {
+    fn new() -> Self {
+        Self {
+            quotas: HashMap::new(),
+        }
+    }
+
+    fn quota(mut self, cave: &'q str, visits: usize) -> Self {
+        self.quotas.insert(cave, visits);
+        self
+    }
+
+    fn of(&self, graph: &Graph<'_>, cave: CaveId) -> Option<usize> {
+        self.quotas
+            .get(graph.names[cave])
+            .copied()
+            .or_else(|| graph.small[cave].map(|_| 1))
+    }
+}
+
+impl Policy for Quotas<'_> {
+    // the amount of visits of each cave
+    type State = Vec<usize>;
+
+    fn initial(&self, graph: &Graph<'_>) -> Self::State {
+        vec![0; graph.names.len()]
+    }
 
-            match current.is_small() {
-                true if visited_twice => return 0,
-                true => visited_twice = true,
-                false => {}
+    fn enter(&self, graph: &Graph<'_>, cave: CaveId, visits: &Self::State) -> Option<Self::State> {
+        match self.of(graph, cave) {
+            Some(quota) if visits[cave] >= quota => None,
+            Some(_) => {
+                let mut visits = visits.clone();
+                visits[cave] += 1;
+                Some(visits)
             }
+            None => Some(visits.clone()),
         }
+    }
+}
 
-        visited.push(current);
+/// A depth first traversal of the graph, which yields each path upon reaching the end cave
+struct Paths<'g, 's, P: Policy> {
+    graph: &'g Graph<'s>,
+    policy: &'g P,
+    start: CaveId,
+    end: CaveId,
+    // the path so far
+    stack: Vec<Frame<P::State>>,
+    // only yield paths which pass through this cave
+    through: Option<CaveId>,
+    // only yield paths of at most this many caves (including start and end)
+    max_len: Option<usize>,
+    // stop after yielding this many paths
+    limit: Option<usize>,
+}
 
-        self.neighbours(current)
-            .iter()
-            .map(|cave| self.dfs(visited.clone(), cave, visited_twice))
-            .sum()
+struct Frame<S> {
+    cave: CaveId,
+    // the index of the next neighbour to try
+    next: usize,
+    // the policy state after entering this cave
+    state: S,
+}
+
+impl<'g, 's, P: Policy> Paths<'g, 's, P> {
+    fn through(self, cave: &str) -> Self {
+        Self {
+            through: Some(self.graph.id(cave)),
+            ..self
+        }
     }
 
-    fn neighbours(&self, node: &'s str) -> &Vec<&'s str> {
-        self.vertices.get(node).unwrap()
+    fn max_len(self, caves: usize) -> Self {
+        Self {
+            max_len: Some(caves),
+            ..self
+        }
+    }
+
+    fn limit(self, paths: usize) -> Self {
+        Self {
+            limit: Some(paths),
+            ..self
+        }
+    }
+}
+
+impl<'g, 's, P: Policy> Iterator for Paths<'g, 's, P> {
+    type Item = Vec<&'s str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.limit == Some(0) {
+            return None;
+        }
+
+        let (start, end) = (self.start, self.end);
+
+        loop {
+            let depth = self.stack.len();
+            let frame = self.stack.last_mut()?;
+
+            if frame.cave == end {
+                let passes_through = self
+                    .through
+                    .map(|through| self.stack.iter().any(|frame| frame.cave == through))
+                    .unwrap_or(true);
+
+                let path = passes_through.then(|| {
+                    self.stack
+                        .iter()
+                        .map(|frame| self.graph.names[frame.cave])
+                        .collect()
+                });
+
+                self.stack.pop();
+
+                if path.is_some() {
+                    self.limit = self.limit.map(|limit| limit - 1);
+                    return path;
+                }
+
+                continue;
+            }
+
+            // Paths can only grow, so once at the maximum length, only the end cave can follow,
+            // and it would make the path too long
+            if self
+                .max_len
+                .map(|max_len| depth >= max_len)
+                .unwrap_or(false)
+            {
+                self.stack.pop();
+                continue;
+            }
+
+            match self.graph.neighbours[frame.cave].get(frame.next) {
+                Some(&cave) => {
+                    frame.next += 1;
+
+                    let state = match cave {
+                        cave if cave == end => Some(frame.state.clone()),
+                        cave if cave == start => None,
+                        cave => self.policy.enter(self.graph, cave, &frame.state),
+                    };
+
+                    if let Some(state) = state {
+                        self.stack.push(Frame {
+                            cave,
+                            next: 0,
+                            state,
+                        });
+                    }
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+// Not necessary for the solution, but useful to look at a cave system before counting its paths
+struct DotFmt<'g, 's> {
+    graph: &'g Graph<'s>,
+    // the edges of the highlighted path, if any, with the lowest cave id first
+    path: HashSet<(CaveId, CaveId)>,
+}
+
+impl<'g, 's> DotFmt<'g, 's> {
+    fn new(graph: &'g Graph<'s>, path: Option<&[&str]>) -> Self {
+        let path = path
+            .unwrap_or_default()
+            .windows(2)
+            .map(|edge| {
+                let (s, t) = (graph.id(edge[0]), graph.id(edge[1]));
+                (s.min(t), s.max(t))
+            })
+            .collect();
+
+        Self { graph, path }
+    }
+
+    fn on_path(&self, cave: CaveId) -> bool {
+        self.path.iter().any(|&(s, t)| s == cave || t == cave)
+    }
+}
+
+impl Display for DotFmt<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "graph caves {{")?;
+
+        for (cave, name) in self.graph.names.iter().enumerate() {
+            // big caves are boxes, small caves are ellipses, and the endpoints are double circles
+            let mut attributes = match self.graph.small[cave] {
+                Some(_) => vec!["shape=ellipse"],
+                None => vec!["shape=box"],
+            };
+
+            if Some(cave) == self.graph.start {
+                attributes = vec!["shape=doublecircle", "style=filled", "fillcolor=palegreen"];
+            } else if Some(cave) == self.graph.end {
+                attributes = vec!["shape=doublecircle", "style=filled", "fillcolor=salmon"];
+            }
+
+            if self.on_path(cave) {
+                attributes.extend(["color=red", "penwidth=2"]);
+            }
+
+            writeln!(f, "    \"{}\" [{}];", name, attributes.join(", "))?;
+        }
+
+        for (s, neighbours) in self.graph.neighbours.iter().enumerate() {
+            // the graph is undirected, so each edge is stored twice, but drawn once
+            for &t in neighbours.iter().filter(|&&t| s <= t) {
+                let (s_name, t_name) = (self.graph.names[s], self.graph.names[t]);
+
+                if self.path.contains(&(s, t)) {
+                    writeln!(
+                        f,
+                        "    \"{}\" -- \"{}\" [color=red, penwidth=3];",
+                        s_name, t_name
+                    )?;
+                } else {
+                    writeln!(f, "    \"{}\" -- \"{}\";", s_name, t_name)?;
+                }
+            }
+        }
+
+        writeln!(f, "}}")
     }
 }
 
@@ -79,7 +684,7 @@ trait Cave {
     fn is_small(&self) -> bool;
 }
 
-impl<'s> Cave for &'s str {
+impl Cave for &str {
     fn is_small(&self) -> bool {
         self.chars().next().unwrap().is_lowercase()
     }
@@ -87,14 +692,17 @@ impl<'s> Cave for &'s str {
 
 #[cfg(test)]
 mod tests {
-    use crate::{part1, part2, Graph};
+    use crate::{
+        part1, part2, DotFmt, Graph, OneSmallCaveTwice, OneSmallCaveUpTo, Quotas, SmallCavesOnce,
+    };
+    use std::collections::{HashMap, HashSet};
 
     #[test]
     fn part1_example() {
         let input = include_str!("../../inputs/example/day12.txt");
         let input = Graph::from_str(input);
 
-        assert_eq!(part1(&input), 10);
+        assert_eq!(part1(&input).unwrap(), 10);
     }
 
     #[test]
@@ -102,7 +710,7 @@ mod tests {
         let input = include_str!("../../inputs/day12.txt");
         let input = Graph::from_str(input);
 
-        assert_eq!(part1(&input), 3761);
+        assert_eq!(part1(&input).unwrap(), 3761);
     }
 
     #[test]
@@ -110,13 +718,201 @@ mod tests {
         let input = include_str!("../../inputs/example/day12.txt");
         let input = Graph::from_str(input);
 
-        assert_eq!(part2(&input), 36);
+        assert_eq!(part2(&input).unwrap(), 36);
     }
     #[test]
     fn part2_solution() {
         let input = include_str!("../../inputs/day12.txt");
         let input = Graph::from_str(input);
 
-        assert_eq!(part2(&input), 99138);
+        assert_eq!(part2(&input).unwrap(), 99138);
+    }
+
+    #[test]
+    fn larger_example() {
+        let input = "fs-end\nhe-DX\nfs-he\nstart-DX\npj-DX\nend-zg\nzg-sl\nzg-pj\npj-he\nRW-he\nfs-DX\npj-RW\nzg-RW\nstart-pj\nhe-WI\nzg-he\npj-fs\nstart-RW";
+        let input = Graph::from_str(input);
+
+        assert_eq!(part1(&input).unwrap(), 226);
+        assert_eq!(part2(&input).unwrap(), 3509);
+    }
+
+    #[test]
+    fn paths_example() {
+        let input = include_str!("../../inputs/example/day12.txt");
+        let graph = Graph::from_str(input);
+
+        let paths = graph
+            .paths(&SmallCavesOnce)
+            .unwrap()
+            .map(|path| path.join(","))
+            .collect::<HashSet<_>>();
+
+        let expected = [
+            "start,A,b,A,c,A,end",
+            "start,A,b,A,end",
+            "start,A,b,end",
+            "start,A,c,A,b,A,end",
+            "start,A,c,A,b,end",
+            "start,A,c,A,end",
+            "start,A,end",
+            "start,b,A,c,A,end",
+            "start,b,A,end",
+            "start,b,end",
+        ];
+
+        assert_eq!(paths, expected.iter().map(|p| p.to_string()).collect());
+    }
+
+    #[test]
+    fn paths_agree_with_count() {
+        let input = include_str!("../../inputs/day12.txt");
+        let graph = Graph::from_str(input);
+
+        assert_eq!(graph.paths(&SmallCavesOnce).unwrap().count(), 3761);
+        assert_eq!(graph.paths(&OneSmallCaveTwice).unwrap().count(), 99138);
+    }
+
+    #[test]
+    fn paths_filtered() {
+        let input = include_str!("../../inputs/example/day12.txt");
+        let graph = Graph::from_str(input);
+
+        let through_c = graph
+            .paths(&SmallCavesOnce)
+            .unwrap()
+            .through("c")
+            .collect::<Vec<_>>();
+        assert_eq!(through_c.len(), 5);
+        assert!(through_c.iter().all(|path| path.contains(&"c")));
+
+        let short = graph
+            .paths(&SmallCavesOnce)
+            .unwrap()
+            .max_len(4)
+            .collect::<Vec<_>>();
+        assert_eq!(short.len(), 4);
+        assert!(short.iter().all(|path| path.len() <= 4));
+
+        assert_eq!(graph.paths(&OneSmallCaveTwice).unwrap().limit(5).count(), 5);
+        assert_eq!(
+            graph
+                .paths(&OneSmallCaveTwice)
+                .unwrap()
+                .through("d")
+                .limit(100)
+                .count(),
+            8
+        );
+    }
+
+    #[test]
+    fn one_small_cave_up_to() {
+        let input = include_str!("../../inputs/day12.txt");
+        let graph = Graph::from_str(input);
+
+        assert_eq!(graph.count_paths(&OneSmallCaveUpTo(1)).unwrap(), 3761);
+        assert_eq!(graph.count_paths(&OneSmallCaveUpTo(2)).unwrap(), 99138);
+
+        let example = Graph::from_str(include_str!("../../inputs/example/day12.txt"));
+        let policy = OneSmallCaveUpTo(3);
+        let paths = example.paths(&policy).unwrap().collect::<Vec<_>>();
+
+        assert_eq!(paths.len(), example.count_paths(&policy).unwrap());
+        assert!(paths.len() > 36);
+
+        for path in paths {
+            let small = path
+                .iter()
+                .filter(|cave| cave.chars().all(char::is_lowercase));
+            let repeated = small
+                .map(|cave| (cave, path.iter().filter(|&c| c == cave).count()))
+                .filter(|&(_, count)| count > 1)
+                .collect::<HashMap<_, _>>();
+
+            assert!(repeated.len() <= 1 && repeated.values().all(|&count| count <= 3));
+        }
+    }
+
+    #[test]
+    fn quotas() {
+        let input = include_str!("../../inputs/example/day12.txt");
+        let graph = Graph::from_str(input);
+
+        // by default, quotas are the same as in part 1
+        assert_eq!(graph.count_paths(&Quotas::new()).unwrap(), 10);
+
+        // big caves may be given a quota too
+        let policy = Quotas::new().quota("A", 1);
+        assert_eq!(graph.count_paths(&policy).unwrap(), 4);
+        assert_eq!(graph.paths(&policy).unwrap().count(), 4);
+
+        // only b may be visited twice
+        let policy = Quotas::new().quota("b", 2);
+        let expected = graph
+            .paths(&OneSmallCaveTwice)
+            .unwrap()
+            .filter(|path| path.iter().filter(|&&cave| cave == "c").count() <= 1)
+            .count();
+        assert_eq!(graph.count_paths(&policy).unwrap(), expected);
+    }
+
+    #[test]
+    fn endpoints() {
+        let input = include_str!("../../inputs/example/day12.txt");
+        let graph = Graph::from_str(input).with_endpoints("b", "end").unwrap();
+
+        assert_eq!(graph.count_paths(&SmallCavesOnce).unwrap(), 8);
+        assert!(graph
+            .paths(&SmallCavesOnce)
+            .unwrap()
+            .all(|path| path[0] == "b" && path[path.len() - 1] == "end"));
+
+        assert!(Graph::from_str(input).with_endpoints("b", "b").is_err());
+        assert!(Graph::from_str(input).with_endpoints("x", "end").is_err());
+    }
+
+    #[test]
+    fn endpoints_without_start_and_end() {
+        let input = "a-B\nB-c\nc-d\nB-d";
+
+        assert!(part1(&Graph::from_str(input)).is_err());
+        assert!(Graph::from_str(input).paths(&SmallCavesOnce).is_err());
+
+        let graph = Graph::from_str(input).with_endpoints("a", "d").unwrap();
+
+        // a,B,d and a,B,c,d and a,B,c,B,d
+        assert_eq!(part1(&graph).unwrap(), 3);
+        assert_eq!(graph.paths(&SmallCavesOnce).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn dot() {
+        let input = include_str!("../../inputs/example/day12.txt");
+        let graph = Graph::from_str(input);
+
+        let dot = DotFmt::new(&graph, None).to_string();
+
+        assert!(dot.starts_with("graph caves {\n"));
+        assert!(dot.contains("\"start\" [shape=doublecircle, style=filled, fillcolor=palegreen];"));
+        assert!(dot.contains("\"end\" [shape=doublecircle, style=filled, fillcolor=salmon];"));
+        assert!(dot.contains("\"A\" [shape=box];"));
+        assert!(dot.contains("\"b\" [shape=ellipse];"));
+        assert_eq!(dot.matches(" -- ").count(), 7);
+        assert!(!dot.contains("color=red"));
+    }
+
+    #[test]
+    fn dot_highlighted_path() {
+        let input = include_str!("../../inputs/example/day12.txt");
+        let graph = Graph::from_str(input);
+
+        let path = ["start", "A", "c", "A", "end"];
+        let dot = DotFmt::new(&graph, Some(&path)).to_string();
+
+        // start-A, A-c and A-end
+        assert_eq!(dot.matches("[color=red, penwidth=3]").count(), 3);
+        assert!(dot.contains("\"c\" [shape=ellipse, color=red, penwidth=2];"));
+        assert!(dot.contains("\"b\" [shape=ellipse];"));
     }
 }
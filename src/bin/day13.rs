@@ -7,21 +7,109 @@ use std::str::FromStr;
 fn main() -> anyhow::Result<()> {
     let contents = include_str!("../../inputs/day13.txt");
 
-    let (dots, folds) = parse(contents)?;
-    let part1 = part1(&dots, &folds[0]);
+    let (paper, folds) = parse(contents)?;
+
+    // Optionally, fold the shorter half onto the longer one, instead of refusing to fold when
+    // the bottom or right half is longer: --fold-mode <standard|shorter-onto-longer>.
+    // Save the paper as a PBM image, after the first few folds: --save-pbm <file>
+    // [--folds <n>]; the paper to save may also be loaded from an image: --load-pbm <file>.
+    // Or generate a new puzzle for a message: --generate <message> <file> [--unfolds <n>]
+    // [--seed <n>]
+    let mut args = std::env::args().skip(1);
+    let mut mode = FoldMode::Standard;
+    let mut load = None;
+    let mut save = None;
+    let mut fold_count = folds.len();
+    let mut generate = None;
+    let mut unfolds = 12;
+    let mut seed = None;
+
+    let number = |arg: Option<String>| -> anyhow::Result<u64> {
+        let arg = arg.with_context(|| anyhow!("Expected a number"))?;
+        arg.parse()
+            .with_context(|| anyhow!("Unable to parse '{}' as a number", arg))
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fold-mode" => {
+                mode = args
+                    .next()
+                    .with_context(|| anyhow!("Expected a fold mode"))?
+                    .parse()?
+            }
+            "--load-pbm" => {
+                let file = args
+                    .next()
+                    .with_context(|| anyhow!("Expected a file after '--load-pbm'"))?;
+                load = Some(file);
+            }
+            "--save-pbm" => {
+                let file = args
+                    .next()
+                    .with_context(|| anyhow!("Expected a file after '--save-pbm'"))?;
+                save = Some(file);
+            }
+            "--folds" => fold_count = number(args.next())? as usize,
+            "--generate" => {
+                let message = args
+                    .next()
+                    .with_context(|| anyhow!("Expected a message after '--generate'"))?;
+                let file = args
+                    .next()
+                    .with_context(|| anyhow!("Expected a file after the message"))?;
+                generate = Some((message, file));
+            }
+            "--unfolds" => unfolds = number(args.next())? as usize,
+            "--seed" => seed = Some(number(args.next())?),
+            _ => bail!("Unknown argument '{}'", arg),
+        }
+    }
+
+    let part1 = part1(&paper, &folds[0], mode)?;
     println!("(day 13) part 1: {}", part1);
 
-    println!("(day 13) part 2:");
-    let stdout = std::io::stdout();
-    let mut lock = stdout.lock();
+    let part2 = part2(paper.clone(), folds.iter(), mode)?;
+    println!("(day 13) part 2: {}", part2);
+
+    if let Some(file) = save {
+        let paper = match load {
+            Some(file) => {
+                let bytes =
+                    std::fs::read(&file).with_context(|| anyhow!("Unable to read '{}'", file))?;
+                Paper::from_pbm(&bytes)?
+            }
+            None => paper,
+        };
+
+        let paper = fold_all(paper, folds.iter().take(fold_count), mode)?;
+        let file =
+            std::fs::File::create(&file).with_context(|| anyhow!("Unable to create '{}'", file))?;
+
+        paper.write_pbm(&mut std::io::BufWriter::new(file))?;
+    }
+
+    if let Some((message, file)) = generate {
+        let seed = seed.unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|time| time.as_nanos() as u64)
+                .unwrap_or(0)
+        });
 
-    part2(dots, folds.iter(), &mut lock)?;
+        let mut rng = Rng::new(seed);
+        let paper = render(&message)?;
+        let folds = random_folds(&paper, unfolds, &mut rng);
+        let puzzle = generate_puzzle(&message, &folds, &mut rng)?;
+
+        std::fs::write(&file, puzzle).with_context(|| anyhow!("Unable to write '{}'", file))?;
+    }
 
     Ok(())
 }
 
 // parse to coordinates (dots) and instructions (folds)
-fn parse(input: &str) -> anyhow::Result<(HashSet<Dot>, Vec<Fold>)> {
+fn parse(input: &str) -> anyhow::Result<(Paper, Vec<Fold>)> {
     let (dots, folds) = input.split_once("\n\n").unwrap();
 
     let dots = dots
@@ -29,49 +117,366 @@ fn parse(input: &str) -> anyhow::Result<(HashSet<Dot>, Vec<Fold>)> {
         .map(|line| line.parse::<Dot>())
         .collect::<anyhow::Result<HashSet<_>>>()?;
 
+    if let Some(dot) = dots.iter().find(|dot| dot.0 < 0 || dot.1 < 0) {
+        bail!("Dots can't have negative coordinates, but found {:?}", dot);
+    }
+
     let folds = folds
         .lines()
         .map(|line| line.parse::<Fold>())
         .collect::<anyhow::Result<Vec<_>>>()?;
 
-    Ok((dots, folds))
+    Ok((Paper::new(dots), folds))
 }
 
-fn part1(dots: &HashSet<Dot>, fold: &Fold) -> usize {
-    fold.fold(dots.iter()).len()
+fn part1(paper: &Paper, fold: &Fold, mode: FoldMode) -> anyhow::Result<usize> {
+    Ok(fold.fold(paper, mode)?.dots.len())
 }
 
-fn part2<'f, W: Write>(
-    dots: HashSet<Dot>,
+fn part2<'f>(
+    paper: Paper,
     folds: impl Iterator<Item = &'f Fold>,
-    output: &mut W,
-) -> anyhow::Result<()> {
-    let code = folds.fold(dots, |acc, next| next.fold(acc.iter()));
-    let code = PaperFmt::new(&code);
+    mode: FoldMode,
+) -> anyhow::Result<String> {
+    let code = fold_all(paper, folds, mode)?;
+
+    ocr(&code.dots)
+}
 
-    writeln!(output, "{}", code).with_context(|| anyhow!("Unable to write code to output"))
+fn fold_all<'f>(
+    paper: Paper,
+    mut folds: impl Iterator<Item = &'f Fold>,
+    mode: FoldMode,
+) -> anyhow::Result<Paper> {
+    folds.try_fold(paper, |acc, next| next.fold(&acc, mode))
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 struct Dot(i32, i32);
 
+/// A sheet of transparent paper; its size is the smallest which fits all of its dots, unless
+/// it's the result of a fold
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Paper {
+    dots: HashSet<Dot>,
+    width: i32,
+    height: i32,
+}
+
+impl Paper {
+    fn new(dots: HashSet<Dot>) -> Self {
+        let width = dots.iter().map(|dot| dot.0 + 1).max().unwrap_or(0);
+        let height = dots.iter().map(|dot| dot.1 + 1).max().unwrap_or(0);
+
+        Self {
+            dots,
+            width,
+            height,
+        }
+    }
+
+    /// Reads a paper from a PBM image, in either the plain (P1) or raw (P4) format, where each
+    /// black pixel is a dot
+    fn from_pbm(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut pos = 0;
+
+        let magic = pbm_token(bytes, &mut pos);
+        let mut number = |what: &str| -> anyhow::Result<i32> {
+            let token = pbm_token(bytes, &mut pos)
+                .with_context(|| anyhow!("Missing the {} of the PBM image", what))?;
+
+            std::str::from_utf8(token)
+                .ok()
+                .and_then(|token| token.parse().ok())
+                .filter(|&number| number >= 0)
+                .with_context(|| anyhow!("Invalid {} in the PBM image", what))
+        };
+
+        let width = number("width")?;
+        let height = number("height")?;
+
+        let pixels = match magic {
+            Some(b"P1") => bytes[pos..]
+                .split(|&b| b == b'\n')
+                // comments run until the end of the line
+                .flat_map(|line| line.split(|&b| b == b'#').next().unwrap_or_default())
+                .filter(|b| !b.is_ascii_whitespace())
+                .map(|&b| match b {
+                    b'0' => Ok(false),
+                    b'1' => Ok(true),
+                    _ => Err(anyhow!("Invalid pixel '{}' in the PBM image", b as char)),
+                })
+                .collect::<anyhow::Result<Vec<bool>>>()?,
+            Some(b"P4") => {
+                // a single whitespace character separates the header from the raster, in which
+                // each row is padded to a whole amount of bytes
+                let raster = bytes.get(pos + 1..).unwrap_or_default();
+                let row_len = (width as usize + 7) / 8;
+
+                raster
+                    .chunks(row_len.max(1))
+                    .take(height as usize)
+                    .flat_map(|row| {
+                        (0..width as usize).map(move |x| {
+                            row.get(x / 8)
+                                .map(|byte| byte & (0x80 >> (x % 8)) != 0)
+                                .unwrap_or(false)
+                        })
+                    })
+                    .collect::<Vec<bool>>()
+            }
+            _ => bail!("Not a PBM image; only the P1 and P4 formats are supported"),
+        };
+
+        if pixels.len() < (width * height) as usize {
+            bail!(
+                "The PBM image should have {} pixels, but has only {}",
+                width * height,
+                pixels.len()
+            );
+        }
+
+        let dots = pixels
+            .iter()
+            .take((width * height) as usize)
+            .enumerate()
+            .filter(|&(_, &black)| black)
+            .map(|(i, _)| Dot(i as i32 % width, i as i32 / width))
+            .collect();
+
+        Ok(Self {
+            dots,
+            width,
+            height,
+        })
+    }
+
+    /// Writes the paper as a plain (P1) PBM image, where each dot is a black pixel
+    fn write_pbm<W: Write>(&self, output: &mut W) -> anyhow::Result<()> {
+        let mut image = format!("P1\n{} {}\n", self.width, self.height);
+
+        for y in 0..self.height {
+            let row = (0..self.width)
+                .map(|x| match self.dots.contains(&Dot(x, y)) {
+                    true => "1",
+                    false => "0",
+                })
+                .collect::<Vec<_>>();
+
+            image.push_str(&row.join(" "));
+            image.push('\n');
+        }
+
+        output
+            .write_all(image.as_bytes())
+            .and_then(|_| output.flush())
+            .with_context(|| anyhow!("Unable to write PBM image"))
+    }
+}
+
+// The next whitespace separated token of a PBM header, skipping comments
+fn pbm_token<'b>(bytes: &'b [u8], pos: &mut usize) -> Option<&'b [u8]> {
+    loop {
+        match bytes.get(*pos)? {
+            b'#' => {
+                while bytes.get(*pos).map(|&b| b != b'\n').unwrap_or(false) {
+                    *pos += 1;
+                }
+            }
+            b if b.is_ascii_whitespace() => *pos += 1,
+            _ => break,
+        }
+    }
+
+    let start = *pos;
+
+    while bytes
+        .get(*pos)
+        .map(|b| !b.is_ascii_whitespace())
+        .unwrap_or(false)
+    {
+        *pos += 1;
+    }
+
+    Some(&bytes[start..*pos])
+}
+
+/// How to fold when the half which is folded over is longer than the half it's folded onto
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum FoldMode {
+    /// Always fold the bottom half up, or the right half to the left, and refuse to fold when
+    /// that would put dots beyond the edge of the paper
+    Standard,
+    /// Fold whichever half is shorter onto the longer one
+    ShorterOntoLonger,
+}
+
+impl FromStr for FoldMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "standard" => Ok(Self::Standard),
+            "shorter-onto-longer" => Ok(Self::ShorterOntoLonger),
+            _ => bail!(
+                "Unknown fold mode '{}', expected one of: standard, shorter-onto-longer",
+                s
+            ),
+        }
+    }
+}
+
 impl Fold {
-    fn fold<'dot>(&self, dots: impl Iterator<Item = &'dot Dot>) -> HashSet<Dot> {
-        #[inline]
-        fn shift(pos: i32, by: i32) -> i32 {
-            by - (by - pos).abs()
+    fn fold(&self, paper: &Paper, mode: FoldMode) -> anyhow::Result<Paper> {
+        let (line, len) = match *self {
+            Fold::Up { y } => (y, paper.height),
+            Fold::Left { x } => (x, paper.width),
+        };
+
+        // The size of a paper may be inferred from its dots, so there may well be more paper
+        // beyond the last dot: we can only be sure which half is shorter when it's on the paper
+        if line < 0 || (line >= len && mode == FoldMode::ShorterOntoLonger) {
+            bail!(
+                "Can't '{}', as the paper is only {} long along this axis",
+                self,
+                len
+            );
+        }
+
+        let mut on_line = paper
+            .dots
+            .iter()
+            .filter(|dot| self.position(dot) == line)
+            .collect::<Vec<_>>();
+
+        if !on_line.is_empty() {
+            on_line.sort_unstable_by_key(|dot| (dot.1, dot.0));
+            bail!(
+                "Can't '{}', as dots lie on the fold line: {:?}",
+                self,
+                on_line
+            );
+        }
+
+        // the lengths of the half which stays in place, and the half which is folded over it
+        let (kept, folded) = (line, len - line - 1);
+        let shorter_onto_longer = folded > kept && mode == FoldMode::ShorterOntoLonger;
+
+        let mut beyond = paper
+            .dots
+            .iter()
+            .filter(|dot| self.position(dot) > 2 * line)
+            .collect::<Vec<_>>();
+
+        if !beyond.is_empty() && mode == FoldMode::Standard {
+            beyond.sort_unstable_by_key(|dot| (dot.1, dot.0));
+            bail!(
+                "Can't '{}', as the folded half ({}) is longer than the half it's folded onto \
+                 ({}), which would place dots beyond the edge of the paper: {:?}",
+                self,
+                folded,
+                kept,
+                beyond
+            );
+        }
+
+        let shift = |pos: i32| match (shorter_onto_longer, pos > line) {
+            // the far half is mirrored onto the near half
+            (false, true) => 2 * line - pos,
+            (false, false) => pos,
+            // the near half is mirrored onto the far half, which now starts at 0
+            (true, true) => pos - line - 1,
+            (true, false) => line - pos - 1,
+        };
+
+        let dots = paper
+            .dots
+            .iter()
+            .map(|dot| match *self {
+                Fold::Up { .. } => Dot(dot.0, shift(dot.1)),
+                Fold::Left { .. } => Dot(shift(dot.0), dot.1),
+            })
+            .collect();
+
+        let len = match mode {
+            FoldMode::Standard => kept,
+            FoldMode::ShorterOntoLonger => kept.max(folded),
+        };
+
+        let (width, height) = match *self {
+            Fold::Up { .. } => (paper.width, len),
+            Fold::Left { .. } => (len, paper.height),
+        };
+
+        Ok(Paper {
+            dots,
+            width,
+            height,
+        })
+    }
+
+    /// The inverse of a standard fold: mirrors the paper across the fold line, which must lie
+    /// beyond the edge of the paper. Each dot randomly stays where it is, moves to its mirror
+    /// image, or ends up in both places; folding the result reproduces the original paper.
+    fn unfold(&self, paper: &Paper, rng: &mut Rng) -> anyhow::Result<Paper> {
+        let (line, len) = match *self {
+            Fold::Up { y } => (y, paper.height),
+            Fold::Left { x } => (x, paper.width),
+        };
+
+        if line < len {
+            bail!(
+                "Can't unfold '{}', as the fold line lies within the paper, which is {} long \
+                 along this axis",
+                self,
+                len
+            );
         }
 
-        dots.into_iter().fold(HashSet::new(), |mut acc, dot| {
-            let res = match *self {
-                Fold::Up(by) => Dot(shift(dot.0, by), dot.1),
-                Fold::Left(by) => Dot(dot.0, shift(dot.1, by)),
+        let mirror = |dot: &Dot| match *self {
+            Fold::Up { .. } => Dot(dot.0, 2 * line - dot.1),
+            Fold::Left { .. } => Dot(2 * line - dot.0, dot.1),
+        };
+
+        let mut dots = paper.dots.iter().collect::<Vec<_>>();
+        // iterate in a fixed order, so a seed always generates the same puzzle
+        dots.sort_unstable_by_key(|dot| (dot.1, dot.0));
+
+        let mut unfolded = HashSet::new();
+
+        for dot in &dots {
+            match rng.below(3) {
+                0 => unfolded.insert(**dot),
+                1 => unfolded.insert(mirror(dot)),
+                _ => unfolded.insert(**dot) | unfolded.insert(mirror(dot)),
             };
+        }
 
-            acc.insert(res);
-            acc
+        // Some dot must end up beyond the fold line, or it would lie outside of the paper
+        // when the paper's size is derived from the dots
+        if let Some(dot) = dots.first() {
+            unfolded.insert(mirror(dot));
+        }
+
+        let (width, height) = match *self {
+            Fold::Up { .. } => (paper.width, 2 * line + 1),
+            Fold::Left { .. } => (2 * line + 1, paper.height),
+        };
+
+        Ok(Paper {
+            dots: unfolded,
+            width,
+            height,
         })
     }
+
+    // the coordinate of a dot which is affected by this fold
+    fn position(&self, dot: &Dot) -> i32 {
+        match self {
+            Fold::Up { .. } => dot.1,
+            Fold::Left { .. } => dot.0,
+        }
+    }
 }
 
 impl FromStr for Dot {
@@ -93,10 +498,12 @@ impl FromStr for Dot {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+/// Folding along a horizontal line `y` folds the bottom half up, and folding along a vertical
+/// line `x` folds the right half to the left
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 enum Fold {
-    Up(i32),
-    Left(i32),
+    Up { y: i32 },
+    Left { x: i32 },
 }
 
 impl FromStr for Fold {
@@ -112,13 +519,22 @@ impl FromStr for Fold {
             .with_context(|| anyhow!("Unable to parse fold value from '{}'", value))?;
 
         Ok(match axis {
-            "fold along y" => Fold::Left(value),
-            "fold along x" => Fold::Up(value),
+            "fold along y" => Fold::Up { y: value },
+            "fold along x" => Fold::Left { x: value },
             _ => bail!("Invalid fold instruction '{}'", axis),
         })
     }
 }
 
+impl Display for Fold {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Fold::Up { y } => write!(f, "fold along y={}", y),
+            Fold::Left { x } => write!(f, "fold along x={}", x),
+        }
+    }
+}
+
 struct PaperFmt<'s> {
     dots: &'s HashSet<Dot>,
 }
@@ -159,25 +575,230 @@ impl Display for PaperFmt<'_> {
     }
 }
 
+/// Each glyph of the font is 4 dots wide, and 6 dots high. Glyphs are separated by an empty
+/// column. Not every letter of the alphabet has made an appearance (yet).
+const GLYPH_WIDTH: i32 = 4;
+const GLYPH_HEIGHT: i32 = 6;
+
+const GLYPHS: [(char, [&str; GLYPH_HEIGHT as usize]); 17] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+/// Reads the letters spelled out by the dots, using the glyph font
+fn ocr(dots: &HashSet<Dot>) -> anyhow::Result<String> {
+    // The glyphs start at the left edge of the paper, not at the leftmost dot, as some glyphs
+    // (like 'I') have an empty first column
+    if let Some(dot) = dots.iter().find(|dot| dot.0 < 0 || dot.1 < 0) {
+        bail!(
+            "Unexpected dot at ({}, {}), outside of the paper",
+            dot.0,
+            dot.1
+        );
+    }
+
+    let max_x = dots.iter().map(|dot| dot.0).max().unwrap_or(-1);
+    let max_y = dots.iter().map(|dot| dot.1).max().unwrap_or(-1);
+
+    if max_y >= GLYPH_HEIGHT {
+        bail!(
+            "The dots are {} high, but glyphs are only {} high",
+            max_y + 1,
+            GLYPH_HEIGHT
+        );
+    }
+
+    let glyphs = (max_x + 1 + GLYPH_WIDTH) / (GLYPH_WIDTH + 1);
+
+    (0..glyphs)
+        .map(|position| {
+            let left = position * (GLYPH_WIDTH + 1);
+
+            // the glyph, shifted to the origin
+            let glyph = dots
+                .iter()
+                .filter(|dot| (left..left + GLYPH_WIDTH).contains(&dot.0))
+                .map(|dot| Dot(dot.0 - left, dot.1))
+                .collect::<HashSet<_>>();
+
+            let is_glyph = |rows: &[&str; GLYPH_HEIGHT as usize]| {
+                rows.iter().enumerate().all(|(y, row)| {
+                    row.bytes()
+                        .enumerate()
+                        .all(|(x, b)| (b == b'#') == glyph.contains(&Dot(x as i32, y as i32)))
+                })
+            };
+
+            let letter = GLYPHS
+                .iter()
+                .find(|(_, rows)| is_glyph(rows))
+                .map(|&(letter, _)| letter)
+                .with_context(|| {
+                    anyhow!(
+                        "Unknown glyph at position {} (x = {}):\n{}",
+                        position,
+                        left,
+                        PaperFmt::new(&glyph)
+                    )
+                })?;
+
+            if let Some(dot) = dots.iter().find(|dot| dot.0 == left + GLYPH_WIDTH) {
+                bail!(
+                    "Unexpected dot at ({}, {}), between glyph {} and {}",
+                    dot.0,
+                    dot.1,
+                    position,
+                    position + 1
+                );
+            }
+
+            Ok(letter)
+        })
+        .collect()
+}
+
+/// Renders a message using the glyph font, e.g. to create a puzzle from
+fn render(message: &str) -> anyhow::Result<Paper> {
+    let dots = message
+        .chars()
+        .enumerate()
+        .map(|(position, letter)| {
+            let (_, rows) = GLYPHS
+                .iter()
+                .find(|(l, _)| *l == letter)
+                .with_context(|| anyhow!("No glyph for '{}' (at position {})", letter, position))?;
+
+            let left = position as i32 * (GLYPH_WIDTH + 1);
+
+            Ok(rows.iter().enumerate().flat_map(move |(y, row)| {
+                row.bytes()
+                    .enumerate()
+                    .filter(|&(_, b)| b == b'#')
+                    .map(move |(x, _)| Dot(left + x as i32, y as i32))
+            }))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    let glyphs = message.chars().count() as i32;
+
+    Ok(Paper {
+        dots,
+        width: (glyphs * (GLYPH_WIDTH + 1) - 1).max(0),
+        height: GLYPH_HEIGHT,
+    })
+}
+
+/// Creates a puzzle which, once folded by the given folds, spells out the message. The paper
+/// is unfolded in reverse order, starting with the last fold.
+fn generate_puzzle(message: &str, folds: &[Fold], rng: &mut Rng) -> anyhow::Result<String> {
+    let paper = folds
+        .iter()
+        .rev()
+        .try_fold(render(message)?, |paper, fold| fold.unfold(&paper, rng))?;
+
+    let mut dots = paper.dots.into_iter().collect::<Vec<_>>();
+    dots.sort_unstable_by_key(|dot| (dot.1, dot.0));
+
+    let dots = dots
+        .iter()
+        .map(|dot| format!("{},{}", dot.0, dot.1))
+        .collect::<Vec<_>>();
+    let folds = folds.iter().map(Fold::to_string).collect::<Vec<_>>();
+
+    Ok(format!("{}\n\n{}\n", dots.join("\n"), folds.join("\n")))
+}
+
+/// Picks `count` folds along random axes, which a puzzle for the given paper can be generated
+/// from. Like the puzzle input, each fold line lies exactly halfway the paper.
+fn random_folds(paper: &Paper, count: usize, rng: &mut Rng) -> Vec<Fold> {
+    let (mut width, mut height) = (paper.width, paper.height);
+
+    let mut folds = (0..count)
+        .map(|_| {
+            if rng.below(2) == 0 {
+                let y = height;
+                height = 2 * y + 1;
+                Fold::Up { y }
+            } else {
+                let x = width;
+                width = 2 * x + 1;
+                Fold::Left { x }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // these are the unfolds, in order; the puzzle folds in reverse order
+    folds.reverse();
+    folds
+}
+
+/// A small xorshift pseudo random number generator; good enough to scatter some dots
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // the state must never be zero, or it stays zero forever; which the xor gives for
+        // exactly one seed
+        match seed ^ 0x9e37_79b9_7f4a_7c15 {
+            0 => Self(0x9e37_79b9_7f4a_7c15),
+            state => Self(state),
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number in `0..n`
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{parse, part1, part2};
+    use crate::{
+        fold_all, generate_puzzle, ocr, parse, part1, part2, random_folds, render, Dot, Fold,
+        FoldMode, Paper, PaperFmt, Rng, GLYPHS, GLYPH_WIDTH,
+    };
+    use std::collections::HashSet;
 
     #[test]
     fn part1_example() {
         let contents = include_str!("../../inputs/example/day13.txt");
-        let (dots, folds) = parse(contents).unwrap();
+        let (paper, folds) = parse(contents).unwrap();
 
-        let part1 = part1(&dots, &folds[0]);
+        let part1 = part1(&paper, &folds[0], FoldMode::Standard).unwrap();
         assert_eq!(part1, 17);
     }
 
     #[test]
     fn part1_solution() {
         let contents = include_str!("../../inputs/day13.txt");
-        let (dots, folds) = parse(contents).unwrap();
+        let (paper, folds) = parse(contents).unwrap();
 
-        let solution = part1(&dots, &folds[0]);
+        let solution = part1(&paper, &folds[0], FoldMode::Standard).unwrap();
 
         assert_eq!(solution, 607);
     }
@@ -185,11 +806,10 @@ mod tests {
     #[test]
     fn part2_example() {
         let contents = include_str!("../../inputs/example/day13.txt");
-        let (dots, folds) = parse(contents).unwrap();
+        let (paper, folds) = parse(contents).unwrap();
 
-        let mut buffer = Vec::new();
-        part2(dots, folds.iter(), &mut buffer).unwrap();
-        let code = String::from_utf8(buffer).unwrap();
+        let code = fold_all(paper.clone(), folds.iter(), FoldMode::Standard).unwrap();
+        let code = PaperFmt::new(&code.dots).to_string();
 
         let expected = r#"
 #####
@@ -199,16 +819,21 @@ mod tests {
 #####"#;
 
         assert_eq!(code.trim(), expected.trim());
+
+        // the example is a square, not a letter
+        let err = part2(paper, folds.iter(), FoldMode::Standard)
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("Unknown glyph at position 0 (x = 0)"));
     }
 
     #[test]
     fn part2_solution() {
         let contents = include_str!("../../inputs/day13.txt");
-        let (dots, folds) = parse(contents).unwrap();
+        let (paper, folds) = parse(contents).unwrap();
 
-        let mut buffer = Vec::new();
-        part2(dots, folds.iter(), &mut buffer).unwrap();
-        let code = String::from_utf8(buffer).unwrap();
+        let code = fold_all(paper.clone(), folds.iter(), FoldMode::Standard).unwrap();
+        let code = PaperFmt::new(&code.dots).to_string();
 
         let expected = r#"
 .##..###..####.#....###..####.####.#...
@@ -219,5 +844,310 @@ mod tests {
 .##..#....####.####.#....#....####.####"#;
 
         assert_eq!(code.trim(), expected.trim());
+        assert_eq!(
+            part2(paper, folds.iter(), FoldMode::Standard).unwrap(),
+            "CPZLPFZL"
+        );
+    }
+
+    #[test]
+    fn ocr_unknown_glyph() {
+        // an 'H', and a 'T', which has not appeared in a puzzle yet
+        let paper = "#..#.###\n#..#..#.\n####..#.\n#..#..#.\n#..#..#.\n#..#..#.";
+        let dots = paper
+            .lines()
+            .enumerate()
+            .flat_map(|(y, row)| {
+                row.bytes()
+                    .enumerate()
+                    .filter(|&(_, b)| b == b'#')
+                    .map(move |(x, _)| Dot(x as i32, y as i32))
+            })
+            .collect::<HashSet<_>>();
+
+        let err = ocr(&dots).unwrap_err().to_string();
+        assert!(
+            err.starts_with("Unknown glyph at position 1 (x = 5)"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn ocr_empty() {
+        assert_eq!(ocr(&HashSet::new()).unwrap(), "");
+    }
+
+    #[test]
+    fn ocr_leading_empty_column() {
+        // the 'I' doesn't use its first column, so the glyphs must line up with the paper,
+        // not with the leftmost dot
+        for message in ["IL", "LI", "II"] {
+            let dots = message
+                .chars()
+                .enumerate()
+                .flat_map(|(position, letter)| {
+                    let (_, rows) = GLYPHS.iter().find(|(c, _)| *c == letter).unwrap();
+                    let left = position as i32 * (GLYPH_WIDTH + 1);
+
+                    rows.iter().enumerate().flat_map(move |(y, row)| {
+                        row.bytes()
+                            .enumerate()
+                            .filter(|&(_, b)| b == b'#')
+                            .map(move |(x, _)| Dot(left + x as i32, y as i32))
+                    })
+                })
+                .collect::<HashSet<_>>();
+
+            assert_eq!(ocr(&dots).unwrap(), message);
+        }
+    }
+
+    fn paper(dots: &[(i32, i32)]) -> Paper {
+        Paper::new(dots.iter().map(|&(x, y)| Dot(x, y)).collect())
+    }
+
+    #[test]
+    fn fold_direction() {
+        assert_eq!("fold along y=7".parse::<Fold>().unwrap(), Fold::Up { y: 7 });
+        assert_eq!(
+            "fold along x=5".parse::<Fold>().unwrap(),
+            Fold::Left { x: 5 }
+        );
+
+        let folded = Fold::Up { y: 2 }
+            .fold(&paper(&[(0, 0), (1, 4)]), FoldMode::Standard)
+            .unwrap();
+        assert_eq!(
+            folded,
+            Paper {
+                dots: [Dot(0, 0), Dot(1, 0)].into_iter().collect(),
+                width: 2,
+                height: 2
+            }
+        );
+
+        let folded = Fold::Left { x: 1 }
+            .fold(&paper(&[(0, 0), (2, 3)]), FoldMode::Standard)
+            .unwrap();
+        assert_eq!(
+            folded,
+            Paper {
+                dots: [Dot(0, 0), Dot(0, 3)].into_iter().collect(),
+                width: 1,
+                height: 4
+            }
+        );
+    }
+
+    #[test]
+    fn fold_through_dots() {
+        let err = Fold::Up { y: 1 }
+            .fold(&paper(&[(0, 0), (3, 1), (0, 2)]), FoldMode::Standard)
+            .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Can't 'fold along y=1', as dots lie on the fold line: [Dot(3, 1)]"
+        );
+    }
+
+    #[test]
+    fn fold_longer_half() {
+        let paper = paper(&[(0, 0), (0, 2), (1, 5)]);
+
+        let err = Fold::Up { y: 1 }
+            .fold(&paper, FoldMode::Standard)
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("the folded half (4) is longer than the half it's folded onto (1)"));
+
+        // the top row is folded down, onto the bottom 4 rows, which now start at 0
+        let folded = Fold::Up { y: 1 }
+            .fold(&paper, FoldMode::ShorterOntoLonger)
+            .unwrap();
+        assert_eq!(folded.height, 4);
+        assert_eq!(folded.dots, [Dot(0, 0), Dot(1, 3)].into_iter().collect());
+
+        // the folded half is longer, but none of its dots end up beyond the edge
+        let paper = Paper {
+            dots: [Dot(0, 0), Dot(1, 3), Dot(0, 4)].into_iter().collect(),
+            width: 2,
+            height: 6,
+        };
+        let folded = Fold::Up { y: 2 }.fold(&paper, FoldMode::Standard).unwrap();
+        assert_eq!(folded.height, 2);
+        assert_eq!(folded.dots, [Dot(0, 0), Dot(1, 1)].into_iter().collect());
+    }
+
+    #[test]
+    fn parse_fold_mode() {
+        assert_eq!(
+            "shorter-onto-longer".parse::<FoldMode>().unwrap(),
+            FoldMode::ShorterOntoLonger
+        );
+        assert_eq!("standard".parse::<FoldMode>().unwrap(), FoldMode::Standard);
+        assert!("inside-out".parse::<FoldMode>().is_err());
+    }
+
+    #[test]
+    fn fold_beyond_last_dot() {
+        // the paper may be larger than its dots tell, folding up the empty part
+        let folded = Fold::Up { y: 7 }
+            .fold(&paper(&[(0, 0), (1, 2)]), FoldMode::Standard)
+            .unwrap();
+
+        assert_eq!(folded.height, 7);
+        assert_eq!(folded.dots, [Dot(0, 0), Dot(1, 2)].into_iter().collect());
+
+        let err = Fold::Left { x: -1 }
+            .fold(&paper(&[(0, 0)]), FoldMode::Standard)
+            .unwrap_err();
+        assert!(err.to_string().starts_with("Can't 'fold along x=-1'"));
+    }
+
+    #[test]
+    fn fold_outside_paper() {
+        let err = Fold::Left { x: 4 }
+            .fold(&paper(&[(1, 1)]), FoldMode::ShorterOntoLonger)
+            .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Can't 'fold along x=4', as the paper is only 2 long along this axis"
+        );
+    }
+
+    #[test]
+    fn pbm_round_trip() {
+        let contents = include_str!("../../inputs/example/day13.txt");
+        let (paper, folds) = parse(contents).unwrap();
+
+        let mut buffer = Vec::new();
+        paper.write_pbm(&mut buffer).unwrap();
+        assert!(buffer.starts_with(b"P1\n11 15\n"));
+        assert_eq!(Paper::from_pbm(&buffer).unwrap(), paper);
+
+        let folded = fold_all(paper, folds.iter(), FoldMode::Standard).unwrap();
+
+        let mut buffer = Vec::new();
+        folded.write_pbm(&mut buffer).unwrap();
+        let expected = "P1\n5 7\n1 1 1 1 1\n1 0 0 0 1\n1 0 0 0 1\n1 0 0 0 1\n1 1 1 1 1\n0 0 0 0 0\n0 0 0 0 0\n";
+        assert_eq!(String::from_utf8(buffer).unwrap(), expected);
+    }
+
+    #[test]
+    fn pbm_plain_with_comments() {
+        let image = b"P1\n# a comment\n3 # the width\n2\n010\n1 0 1 # trailing\n";
+        let paper = Paper::from_pbm(image).unwrap();
+
+        assert_eq!((paper.width, paper.height), (3, 2));
+        assert_eq!(
+            paper.dots,
+            [Dot(1, 0), Dot(0, 1), Dot(2, 1)].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn pbm_raw() {
+        // 10 pixels wide, so each row takes two bytes
+        let image = [
+            b"P4\n10 2\n".as_ref(),
+            &[0b1000_0000, 0b0100_0000, 0b0000_0001, 0],
+        ]
+        .concat();
+        let paper = Paper::from_pbm(&image).unwrap();
+
+        assert_eq!((paper.width, paper.height), (10, 2));
+        assert_eq!(
+            paper.dots,
+            [Dot(0, 0), Dot(9, 0), Dot(7, 1)].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn pbm_invalid() {
+        assert!(Paper::from_pbm(b"P2\n1 1\n0").is_err());
+        assert!(Paper::from_pbm(b"P1\n2 2\n0 1 1").is_err());
+        assert!(Paper::from_pbm(b"P1\n1 1\n2").is_err());
+        assert!(Paper::from_pbm(b"P1\n-1 1\n").is_err());
+    }
+
+    #[test]
+    fn render_reads_back() {
+        let paper = render("CPZLPFZL").unwrap();
+
+        assert_eq!((paper.width, paper.height), (39, 6));
+        assert_eq!(ocr(&paper.dots).unwrap(), "CPZLPFZL");
+        assert!(render("HELLO, WORLD").is_err());
+    }
+
+    #[test]
+    fn generate_with_chosen_folds() {
+        // the folds of the example, on a paper which fits a single letter
+        let folds = [Fold::Up { y: 6 }, Fold::Left { x: 4 }];
+        let puzzle = generate_puzzle("A", &folds, &mut Rng::new(7)).unwrap();
+
+        assert!(puzzle.ends_with("\n\nfold along y=6\nfold along x=4\n"));
+
+        let (paper, folds) = parse(&puzzle).unwrap();
+        assert!(paper.dots.iter().all(|dot| dot.0 <= 8 && dot.1 <= 12));
+        assert_eq!(part2(paper, folds.iter(), FoldMode::Standard).unwrap(), "A");
+    }
+
+    #[test]
+    fn generate_with_random_folds() {
+        for seed in 0..20 {
+            let mut rng = Rng::new(seed);
+            let folds = random_folds(&render("HELLO").unwrap(), 8, &mut rng);
+            let puzzle = generate_puzzle("HELLO", &folds, &mut rng).unwrap();
+
+            let (paper, parsed) = parse(&puzzle).unwrap();
+            assert_eq!(parsed, folds);
+            assert!(part1(&paper, &parsed[0], FoldMode::Standard).unwrap() > 0);
+            assert_eq!(
+                part2(paper, parsed.iter(), FoldMode::Standard).unwrap(),
+                "HELLO",
+                "seed {}",
+                seed
+            );
+        }
+    }
+
+    #[test]
+    fn generate_every_glyph() {
+        // every letter of the font, including those with an empty first column, like 'I'
+        let message = GLYPHS.iter().map(|&(letter, _)| letter).collect::<String>();
+
+        for message in [message.as_str(), "IL"] {
+            for seed in 0..10 {
+                let mut rng = Rng::new(seed);
+                let folds = random_folds(&render(message).unwrap(), 4, &mut rng);
+                let puzzle = generate_puzzle(message, &folds, &mut rng).unwrap();
+
+                let (paper, parsed) = parse(&puzzle).unwrap();
+                assert_eq!(
+                    part2(paper, parsed.iter(), FoldMode::Standard).unwrap(),
+                    message,
+                    "seed {}",
+                    seed
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rng_never_stuck() {
+        let mut rng = Rng::new(0x9e37_79b9_7f4a_7c15);
+        assert!((0..10).any(|_| rng.below(100) != 0));
+    }
+
+    #[test]
+    fn unfold_within_paper() {
+        let paper = render("A").unwrap();
+
+        assert!(Fold::Up { y: 3 }.unfold(&paper, &mut Rng::new(0)).is_err());
+        assert!(Fold::Up { y: 6 }.unfold(&paper, &mut Rng::new(0)).is_ok());
     }
 }
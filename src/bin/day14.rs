@@ -1,121 +1,488 @@
-use std::collections::HashMap;
+use anyhow::{anyhow, bail, Context};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
 
 fn main() -> anyhow::Result<()> {
     let contents = include_str!("../../inputs/day14.txt");
-    let (polymer, rules) = parse(contents);
+    let (polymer, rules) = parse(contents)?;
 
-    let part1 = polymerize(&polymer, &rules, 10);
+    // Optionally, refuse rules which don't cover every pair which may occur: --strict; show how
+    // the amount of each element grows: --history <steps>; or the amount of each element after
+    // very many steps: --steps <steps> [--modulo <m>]
+    let mut args = std::env::args().skip(1);
+    let mut strict = false;
+    let mut history_steps = None;
+    let mut steps = None;
+    let mut arithmetic = Arithmetic::Checked;
+
+    let number = |arg: Option<String>| -> anyhow::Result<u64> {
+        let arg = arg.with_context(|| anyhow!("Expected a number"))?;
+        arg.parse()
+            .with_context(|| anyhow!("Unable to parse '{}' as a number", arg))
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--strict" => strict = true,
+            "--history" => history_steps = Some(number(args.next())? as usize),
+            "--steps" => steps = Some(number(args.next())?),
+            "--modulo" => arithmetic = Arithmetic::Modular(number(args.next())?),
+            _ => bail!("Unknown argument '{}'", arg),
+        }
+    }
+
+    if matches!(arithmetic, Arithmetic::Modular(_)) && steps.is_none() {
+        bail!("--modulo requires --steps");
+    }
+
+    if strict {
+        rules.check(&polymer)?;
+    } else {
+        let gaps = rules.gaps(&polymer);
+
+        if !gaps.is_empty() {
+            eprintln!("(day 14) warning: {}", GapsFmt(&gaps));
+        }
+    }
+
+    let part1 = polymerize(&polymer, &rules, 10)?;
     println!("(day 14) part 1: {}", part1);
 
-    let part2 = polymerize(&polymer, &rules, 40);
+    let part2 = polymerize(&polymer, &rules, 40)?;
     println!("(day 14) part 2: {}", part2);
 
+    if let Some(steps) = history_steps {
+        for (step, histogram) in history(&polymer, &rules, steps)?.iter().enumerate() {
+            let counts = histogram
+                .iter()
+                .map(|(element, count)| format!("{}={}", element, count))
+                .collect::<Vec<_>>();
+
+            println!("{:>4}: {}", step, counts.join(" "));
+        }
+    }
+
+    if let Some(steps) = steps {
+        let histogram = Transition::new(&polymer, &rules).histogram(&polymer, steps, arithmetic)?;
+
+        for (element, count) in histogram {
+            println!("{}: {}", element, count);
+        }
+    }
+
     Ok(())
 }
 
+// parse to the polymer template, and the pair insertion rules
+fn parse(input: &str) -> anyhow::Result<(Vec<char>, RuleSet)> {
+    let (template, rules) = input
+        .split_once("\n\n")
+        .with_context(|| anyhow!("Expected a template, followed by an empty line, and rules"))?;
+
+    let polymer = template.chars().collect();
+
+    // the rules start at the third line, after the template, and an empty line
+    let rules = RuleSet::parse(rules, 3)?;
+
+    Ok((polymer, rules))
+}
+
+/// The pair insertion rules; there's at most one rule for each pair
 #[derive(Debug)]
-struct Rule {
-    window: (char, char),
-    insertion: char,
+struct RuleSet {
+    rules: HashMap<(char, char), char>,
 }
 
-fn parse(input: &str) -> (Vec<char>, Vec<Rule>) {
-    let (template, rules) = input.split_once("\n\n").unwrap();
+impl RuleSet {
+    /// Parses rules of the form `AB -> C`, one per line; `first_line` is the line number of the
+    /// first rule, for error messages
+    fn parse(input: &str, first_line: usize) -> anyhow::Result<Self> {
+        let mut rules = HashMap::new();
+        let mut defined_on = HashMap::new();
 
-    let polymer = template.chars().collect();
+        for (line_number, line) in (first_line..).zip(input.lines()) {
+            let (window, insertion) = line.split_once(" -> ").with_context(|| {
+                anyhow!(
+                    "line {}: expected a rule of the form 'AB -> C', but found '{}'",
+                    line_number,
+                    line
+                )
+            })?;
+
+            let (lhs, rhs) = match window.chars().collect::<Vec<_>>()[..] {
+                [lhs, rhs] => (lhs, rhs),
+                _ => bail!(
+                    "line {}: a rule must match a pair of two elements, but found '{}'",
+                    line_number,
+                    window
+                ),
+            };
 
-    let rules = rules
-        .lines()
-        .map(|s| {
-            let (window, insertion) = s.split_once(" -> ").unwrap();
-            let mut chars = window.chars();
-            let lhs = chars.next().unwrap();
-            let rhs = chars.next().unwrap();
-            let insertion = insertion.chars().next().unwrap();
-
-            Rule {
-                window: (lhs, rhs),
-                insertion,
+            let insertion = match insertion.chars().collect::<Vec<_>>()[..] {
+                [insertion] => insertion,
+                _ => bail!(
+                    "line {}: a rule must insert a single element, but found '{}'",
+                    line_number,
+                    insertion
+                ),
+            };
+
+            if let Some(first) = defined_on.insert((lhs, rhs), line_number) {
+                bail!(
+                    "line {}: duplicate rule for '{}{}', which was already defined on line {}",
+                    line_number,
+                    lhs,
+                    rhs,
+                    first
+                );
             }
-        })
-        .collect();
 
-    (polymer, rules)
-}
+            rules.insert((lhs, rhs), insertion);
+        }
 
-type PolymerPairCounter = HashMap<(char, char), usize>;
+        Ok(Self { rules })
+    }
 
-fn polymerize(polymer: &[char], rules: &[Rule], times: usize) -> usize {
-    let init = polymer
-        .windows(2)
-        .fold(PolymerPairCounter::new(), |mut acc, slice| {
-            *acc.entry((slice[0], slice[1])).or_default() += 1;
-            acc
-        });
+    fn get(&self, pair: (char, char)) -> Option<char> {
+        self.rules.get(&pair).copied()
+    }
+
+    /// All pairs which can occur when growing the polymer, in the order they're discovered
+    fn reachable(&self, polymer: &[char]) -> Vec<(char, char)> {
+        let mut pairs = Vec::new();
+        let mut seen = HashSet::new();
+        let mut todo = polymer
+            .windows(2)
+            .map(|slice| (slice[0], slice[1]))
+            .rev()
+            .collect::<Vec<_>>();
+
+        while let Some(pair) = todo.pop() {
+            if !seen.insert(pair) {
+                continue;
+            }
 
-    let map = (0..times).fold(init, |mut old_pairs, _| {
-        // inner
-        let new_pairs = rules
+            pairs.push(pair);
+
+            if let Some(middle) = self.get(pair) {
+                todo.push((middle, pair.1));
+                todo.push((pair.0, middle));
+            }
+        }
+
+        pairs
+    }
+
+    /// The pairs which can occur when growing the polymer, but for which there's no rule; such
+    /// pairs are left as they are
+    fn gaps(&self, polymer: &[char]) -> Vec<(char, char)> {
+        let mut gaps = self
+            .reachable(polymer)
+            .into_iter()
+            .filter(|&pair| self.get(pair).is_none())
+            .collect::<Vec<_>>();
+
+        gaps.sort_unstable();
+        gaps
+    }
+
+    /// Strict mode: fails if there's a pair which can occur, but has no rule
+    fn check(&self, polymer: &[char]) -> anyhow::Result<()> {
+        let gaps = self.gaps(polymer);
+
+        if !gaps.is_empty() {
+            bail!("{}", GapsFmt(&gaps));
+        }
+
+        Ok(())
+    }
+}
+
+struct GapsFmt<'g>(&'g [(char, char)]);
+
+impl Display for GapsFmt<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let pairs = self
+            .0
             .iter()
-            .fold(PolymerPairCounter::new(), |mut acc, rule| {
-                let (lhs, rhs) = rule.window;
-                let middle = rule.insertion;
-                let size = *old_pairs.get(&rule.window).unwrap_or(&0);
-                *acc.entry((lhs, middle)).or_default() += size;
-                *acc.entry((middle, rhs)).or_default() += size;
-                old_pairs.insert((lhs, rhs), 0);
+            .map(|(lhs, rhs)| format!("{}{}", lhs, rhs))
+            .collect::<Vec<_>>();
+
+        write!(f, "no rule for the pair(s): {}", pairs.join(", "))
+    }
+}
+
+type PolymerPairCounter = HashMap<(char, char), u128>;
+
+/// The amount of each element in a polymer
+type Histogram = BTreeMap<char, u128>;
+
+fn polymerize(polymer: &[char], rules: &RuleSet, times: usize) -> anyhow::Result<u128> {
+    let histogram = histogram(polymer, rules, times)?;
+
+    let max = histogram.values().max().copied().unwrap_or(0);
+    let min = histogram.values().min().copied().unwrap_or(0);
+
+    Ok(max - min)
+}
+
+/// The element counts after the given amount of steps
+fn histogram(polymer: &[char], rules: &RuleSet, steps: usize) -> anyhow::Result<Histogram> {
+    let mut growth = Growth::new(polymer, rules);
 
+    for _ in 0..steps {
+        growth.step()?;
+    }
+
+    Ok(growth.elements)
+}
+
+/// The element counts of the template, followed by the element counts after each step
+fn history(polymer: &[char], rules: &RuleSet, steps: usize) -> anyhow::Result<Vec<Histogram>> {
+    let mut growth = Growth::new(polymer, rules);
+    let mut history = Vec::with_capacity(steps + 1);
+    history.push(growth.elements.clone());
+
+    for _ in 0..steps {
+        growth.step()?;
+        history.push(growth.elements.clone());
+    }
+
+    Ok(history)
+}
+
+/// Tracks a growing polymer by the amount of each pair of adjacent elements, and the amount of
+/// each element. Each insertion adds exactly one element, so the element counts can be kept up
+/// to date alongside the pairs.
+struct Growth<'r> {
+    pairs: PolymerPairCounter,
+    elements: Histogram,
+    rules: &'r RuleSet,
+    step: usize,
+}
+
+impl<'r> Growth<'r> {
+    fn new(polymer: &[char], rules: &'r RuleSet) -> Self {
+        let pairs = polymer
+            .windows(2)
+            .fold(PolymerPairCounter::new(), |mut acc, slice| {
+                *acc.entry((slice[0], slice[1])).or_default() += 1;
                 acc
             });
 
-        old_pairs
+        let elements = polymer.iter().fold(Histogram::new(), |mut acc, &element| {
+            *acc.entry(element).or_default() += 1;
+            acc
+        });
+
+        Self {
+            pairs,
+            elements,
+            rules,
+            step: 0,
+        }
+    }
+
+    fn step(&mut self) -> anyhow::Result<()> {
+        self.step += 1;
+        let step = self.step;
+
+        let overflow = || anyhow!("Counts no longer fit in a u128 at step {}", step);
+        let add = |counter: &mut u128, count: u128| -> anyhow::Result<()> {
+            *counter = counter.checked_add(count).with_context(overflow)?;
+            Ok(())
+        };
+
+        let mut pairs = PolymerPairCounter::with_capacity(self.pairs.len());
+
+        for (&(lhs, rhs), &count) in &self.pairs {
+            match self.rules.get((lhs, rhs)) {
+                Some(middle) => {
+                    add(pairs.entry((lhs, middle)).or_default(), count)?;
+                    add(pairs.entry((middle, rhs)).or_default(), count)?;
+                    add(self.elements.entry(middle).or_default(), count)?;
+                }
+                // pairs without a rule stay as they are
+                None => add(pairs.entry((lhs, rhs)).or_default(), count)?,
+            }
+        }
+
+        self.pairs = pairs;
+
+        Ok(())
+    }
+}
+
+/// How to deal with counts which no longer fit in a u128
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Arithmetic {
+    /// Fail when a count doesn't fit
+    Checked,
+    /// Count modulo some number
+    Modular(u64),
+}
+
+impl Arithmetic {
+    fn add(self, lhs: u128, rhs: u128) -> anyhow::Result<u128> {
+        match self {
+            Arithmetic::Checked => lhs
+                .checked_add(rhs)
+                .with_context(|| anyhow!("Counts no longer fit in a u128")),
+            Arithmetic::Modular(m) => Ok((lhs + rhs) % u128::from(m)),
+        }
+    }
+
+    fn mul(self, lhs: u128, rhs: u128) -> anyhow::Result<u128> {
+        match self {
+            Arithmetic::Checked => lhs
+                .checked_mul(rhs)
+                .with_context(|| anyhow!("Counts no longer fit in a u128")),
+            // both operands are smaller than the modulus, which fits in 64 bits, so their
+            // product fits in 128 bits
+            Arithmetic::Modular(m) => Ok(lhs * rhs % u128::from(m)),
+        }
+    }
+}
+
+type Matrix = Vec<Vec<u128>>;
+
+/// A single step of polymer growth, as a linear map over the pair counts: the column of a pair
+/// holds the pairs it turns into. Taking `n` steps then comes down to raising the matrix to the
+/// `n`th power, which takes a logarithmic amount of matrix multiplications.
+struct Transition {
+    // the pairs which can occur, given the template and the rules
+    pairs: Vec<(char, char)>,
+    index: HashMap<(char, char), usize>,
+    matrix: Matrix,
+}
+
+impl Transition {
+    fn new(polymer: &[char], rules: &RuleSet) -> Self {
+        // only keep track of the pairs reachable from the template
+        let pairs = rules.reachable(polymer);
+        let index = pairs
             .iter()
-            .filter(|(_, &v)| v > 0)
-            .fold(new_pairs, |mut acc, (&pos, &v)| {
-                *acc.entry(pos).or_default() += v;
-                acc
-            })
-    });
+            .enumerate()
+            .map(|(i, &pair)| (pair, i))
+            .collect::<HashMap<_, _>>();
 
-    let counter = map.iter().fold(
-        HashMap::<char, usize>::new(),
-        |mut acc, (&(l, r), &count)| {
-            *acc.entry(l).or_default() += count;
-            *acc.entry(r).or_default() += count;
-            acc
-        },
-    );
-
-    // We counted double because we used .windows(2) above
-    // There are two edge cases: the first and last element, for which we have to add one, for being
-    // only each in one 'window'.
-    let halve = |item: char, count: usize| {
-        if polymer[0] == item || polymer[polymer.len() - 1] == item {
-            count / 2 + 1
-        } else {
-            count / 2
+        let mut matrix = vec![vec![0; pairs.len()]; pairs.len()];
+
+        for (from, pair) in pairs.iter().enumerate() {
+            match rules.get(*pair) {
+                Some(middle) => {
+                    matrix[index[&(pair.0, middle)]][from] += 1;
+                    matrix[index[&(middle, pair.1)]][from] += 1;
+                }
+                None => matrix[from][from] += 1,
+            }
         }
-    };
 
-    let (max, min) = counter
-        .iter()
-        .map(|(&c, &n)| halve(c, n))
-        .fold((usize::MIN, usize::MAX), |(max, min), count| {
-            (max.max(count), min.min(count))
-        });
+        Self {
+            pairs,
+            index,
+            matrix,
+        }
+    }
+
+    /// The element counts after the given amount of steps
+    fn histogram(
+        &self,
+        polymer: &[char],
+        steps: u64,
+        arithmetic: Arithmetic,
+    ) -> anyhow::Result<Histogram> {
+        if arithmetic == Arithmetic::Modular(0) {
+            bail!("Can't count modulo 0");
+        }
+
+        let mut counts = vec![0; self.pairs.len()];
+
+        for slice in polymer.windows(2) {
+            let i = self.index[&(slice[0], slice[1])];
+            counts[i] = arithmetic.add(counts[i], 1)?;
+        }
+
+        // exponentiation by squaring, applying the powers of the matrix directly to the counts
+        let mut power = self.matrix.clone();
+        let mut remaining = steps;
+
+        while remaining > 0 {
+            if remaining & 1 == 1 {
+                counts = multiply_vector(&power, &counts, arithmetic)?;
+            }
+
+            remaining >>= 1;
+
+            if remaining > 0 {
+                power = multiply(&power, &power, arithmetic)?;
+            }
+        }
+
+        // Each element is the first element of exactly one pair, except for the last element
+        // of the polymer, which never changes
+        let mut histogram = Histogram::new();
+
+        for (&(first, _), &count) in self.pairs.iter().zip(&counts) {
+            let entry = histogram.entry(first).or_default();
+            *entry = arithmetic.add(*entry, count)?;
+        }
 
-    max - min
+        if let Some(&last) = polymer.last() {
+            let entry = histogram.entry(last).or_default();
+            *entry = arithmetic.add(*entry, 1)?;
+        }
+
+        // elements which don't occur (yet) are left out, unless counting modulo some number,
+        // where a count of 0 doesn't imply the element is absent
+        if arithmetic == Arithmetic::Checked {
+            histogram.retain(|_, count| *count > 0);
+        }
+
+        Ok(histogram)
+    }
+}
+
+fn multiply(lhs: &Matrix, rhs: &Matrix, arithmetic: Arithmetic) -> anyhow::Result<Matrix> {
+    let n = lhs.len();
+    let mut product = vec![vec![0; n]; n];
+
+    for (i, row) in lhs.iter().enumerate() {
+        for (k, &value) in row.iter().enumerate().filter(|&(_, &value)| value != 0) {
+            for j in 0..n {
+                let term = arithmetic.mul(value, rhs[k][j])?;
+                product[i][j] = arithmetic.add(product[i][j], term)?;
+            }
+        }
+    }
+
+    Ok(product)
+}
+
+fn multiply_vector(
+    matrix: &Matrix,
+    vector: &[u128],
+    arithmetic: Arithmetic,
+) -> anyhow::Result<Vec<u128>> {
+    matrix
+        .iter()
+        .map(|row| {
+            row.iter().zip(vector).try_fold(0, |acc, (&value, &count)| {
+                arithmetic.add(acc, arithmetic.mul(value, count)?)
+            })
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{parse, polymerize};
+    use crate::{histogram, history, parse, polymerize, Arithmetic, RuleSet, Transition};
 
     #[test]
     fn part1_example() {
         let input = include_str!("../../inputs/example/day14.txt");
-        let inputs = parse(input);
-        let solution = polymerize(&inputs.0, &inputs.1, 10);
+        let inputs = parse(input).unwrap();
+        let solution = polymerize(&inputs.0, &inputs.1, 10).unwrap();
 
         assert_eq!(solution, 1588);
     }
@@ -123,8 +490,8 @@ mod tests {
     #[test]
     fn part1_solution() {
         let input = include_str!("../../inputs/day14.txt");
-        let inputs = parse(input);
-        let solution = polymerize(&inputs.0, &inputs.1, 10);
+        let inputs = parse(input).unwrap();
+        let solution = polymerize(&inputs.0, &inputs.1, 10).unwrap();
 
         assert_eq!(solution, 3406);
     }
@@ -132,8 +499,8 @@ mod tests {
     #[test]
     fn part2_example() {
         let input = include_str!("../../inputs/example/day14.txt");
-        let inputs = parse(input);
-        let solution = polymerize(&inputs.0, &inputs.1, 40);
+        let inputs = parse(input).unwrap();
+        let solution = polymerize(&inputs.0, &inputs.1, 40).unwrap();
 
         assert_eq!(solution, 2188189693529);
     }
@@ -141,9 +508,178 @@ mod tests {
     #[test]
     fn part2_solution() {
         let input = include_str!("../../inputs/day14.txt");
-        let inputs = parse(input);
-        let solution = polymerize(&inputs.0, &inputs.1, 40);
+        let inputs = parse(input).unwrap();
+        let solution = polymerize(&inputs.0, &inputs.1, 40).unwrap();
 
         assert_eq!(solution, 3941782230241);
     }
+
+    #[test]
+    fn histogram_example() {
+        let input = include_str!("../../inputs/example/day14.txt");
+        let (polymer, rules) = parse(input).unwrap();
+
+        // after step 10, B occurs 1749 times, C 298 times, H 161 times, and N 865 times
+        let histogram = histogram(&polymer, &rules, 10).unwrap();
+        let expected = [('B', 1749), ('C', 298), ('H', 161), ('N', 865)];
+
+        assert_eq!(histogram, expected.into_iter().collect());
+    }
+
+    #[test]
+    fn history_example() {
+        let input = include_str!("../../inputs/example/day14.txt");
+        let (polymer, rules) = parse(input).unwrap();
+
+        let history = history(&polymer, &rules, 4).unwrap();
+        let lengths = history
+            .iter()
+            .map(|histogram| histogram.values().sum::<u128>())
+            .collect::<Vec<_>>();
+
+        // the polymer grows from 4 elements to 7, 13, 25, and 49 elements
+        assert_eq!(lengths, vec![4, 7, 13, 25, 49]);
+
+        // after step 1: NCNBCHB
+        let expected = [('B', 2), ('C', 2), ('H', 1), ('N', 2)];
+        assert_eq!(history[1], expected.into_iter().collect());
+    }
+
+    #[test]
+    fn overflow() {
+        let input = include_str!("../../inputs/example/day14.txt");
+        let (polymer, rules) = parse(input).unwrap();
+
+        // the polymer roughly doubles in length with every step
+        assert!(histogram(&polymer, &rules, 120).is_ok());
+
+        let err = histogram(&polymer, &rules, 200).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Counts no longer fit in a u128 at step"));
+    }
+
+    #[test]
+    fn transition_agrees_with_growth() {
+        for input in [
+            include_str!("../../inputs/example/day14.txt"),
+            include_str!("../../inputs/day14.txt"),
+        ] {
+            let (polymer, rules) = parse(input).unwrap();
+            let transition = Transition::new(&polymer, &rules);
+
+            for steps in [0, 1, 2, 10, 40, 100] {
+                assert_eq!(
+                    transition
+                        .histogram(&polymer, steps, Arithmetic::Checked)
+                        .unwrap(),
+                    histogram(&polymer, &rules, steps as usize).unwrap(),
+                    "{} steps",
+                    steps
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn transition_modular() {
+        let input = include_str!("../../inputs/example/day14.txt");
+        let (polymer, rules) = parse(input).unwrap();
+        let transition = Transition::new(&polymer, &rules);
+
+        let m = 1_000_000_007;
+        let exact = histogram(&polymer, &rules, 40).unwrap();
+        let modular = transition
+            .histogram(&polymer, 40, Arithmetic::Modular(m))
+            .unwrap();
+
+        for (element, count) in exact {
+            assert_eq!(modular[&element], count % u128::from(m));
+        }
+    }
+
+    #[test]
+    fn transition_huge() {
+        let input = include_str!("../../inputs/example/day14.txt");
+        let (polymer, rules) = parse(input).unwrap();
+        let transition = Transition::new(&polymer, &rules);
+
+        let steps = 1_000_000_000_000_000;
+        assert!(transition
+            .histogram(&polymer, steps, Arithmetic::Checked)
+            .is_err());
+
+        // every pair of the example has a rule, so the polymer goes from `len` elements to
+        // `2 * len - 1` elements with each step, i.e. `(len - 1) * 2^steps + 1` in total
+        let m = 1_000_000_007_u128;
+        let modular = transition
+            .histogram(&polymer, steps, Arithmetic::Modular(m as u64))
+            .unwrap();
+
+        let mut pow = 1;
+        let (mut base, mut exp) = (2, steps);
+        while exp > 0 {
+            if exp & 1 == 1 {
+                pow = pow * base % m;
+            }
+            base = base * base % m;
+            exp >>= 1;
+        }
+
+        let length = modular.values().sum::<u128>() % m;
+        assert_eq!(length, (3 * pow + 1) % m);
+    }
+
+    #[test]
+    fn rules_duplicate() {
+        let err = RuleSet::parse("AB -> C\nBA -> C\nAB -> A", 3).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "line 5: duplicate rule for 'AB', which was already defined on line 3"
+        );
+    }
+
+    #[test]
+    fn rules_malformed() {
+        let err = parse("AB\n\nAB -> C\nABC -> D").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 4: a rule must match a pair of two elements, but found 'ABC'"
+        );
+
+        let err = parse("AB\n\nAB -> CD").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 3: a rule must insert a single element, but found 'CD'"
+        );
+
+        let err = parse("AB\n\nAB => C").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 3: expected a rule of the form 'AB -> C', but found 'AB => C'"
+        );
+    }
+
+    #[test]
+    fn rules_gaps() {
+        let input = include_str!("../../inputs/example/day14.txt");
+        let (polymer, rules) = parse(input).unwrap();
+
+        assert!(rules.gaps(&polymer).is_empty());
+        assert!(rules.check(&polymer).is_ok());
+
+        // AB becomes ACB, but there are no rules for AC and CB
+        let (polymer, rules) = parse("AB\n\nAB -> C\nBB -> A").unwrap();
+
+        assert_eq!(rules.gaps(&polymer), vec![('A', 'C'), ('C', 'B')]);
+        assert_eq!(
+            rules.check(&polymer).unwrap_err().to_string(),
+            "no rule for the pair(s): AC, CB"
+        );
+
+        // which are left as they are
+        let history = history(&polymer, &rules, 2).unwrap();
+        assert_eq!(history[1], history[2]);
+    }
 }
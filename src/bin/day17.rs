@@ -1,50 +1,333 @@
-use std::cmp::Ordering;
+use anyhow::{anyhow, bail, Context};
+use std::collections::HashSet;
 use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::ops::RangeInclusive;
 
 fn main() -> anyhow::Result<()> {
     let input = include_str!("../../inputs/day17.txt").trim();
 
     let target = parse(input);
 
-    println!("(day 17) part 1: {}", part1(&target));
+    let mut args = std::env::args().skip(1);
+    let (mut gravity, mut drag, mut origin) = (1, 1, Position::default());
+    let (mut plot, mut scatter) = (None, false);
+    let mut svg = None;
+
+    let number = |arg: Option<String>| -> anyhow::Result<i32> {
+        let arg = arg.with_context(|| anyhow!("Expected a number"))?;
+        arg.parse()
+            .with_context(|| anyhow!("Unable to parse '{}' as a number", arg))
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--gravity" => gravity = number(args.next())?,
+            "--drag" => drag = number(args.next())?,
+            "--origin" => origin = Position::new(number(args.next())?, number(args.next())?),
+            "--plot" => plot = Some(Velocity::new(number(args.next())?, number(args.next())?)),
+            "--scatter" => scatter = true,
+            "--svg" => svg = Some(args.next().with_context(|| anyhow!("Expected a file"))?),
+            _ => bail!("Unknown argument '{}'", arg),
+        }
+    }
+
+    // Both would be written to the same file, and the scatter plot would replace the trajectory
+    if plot.is_some() && scatter && svg.is_some() {
+        bail!("Only one of '--plot' and '--scatter' can be written to an SVG file at a time");
+    }
+
+    let physics = Physics::new(gravity, drag, origin)?;
 
-    println!("(day 17) part 2: {}", part2(&target));
+    if let Some(velocity) = plot {
+        let plot = PlotFmt::new(&target, &Simulation::new(velocity, physics));
+        output(&plot, svg.as_deref(), |plot, out| plot.write_svg(out))?;
+    }
+
+    if scatter {
+        let scatter = ScatterFmt::new(Solver::new(&target, &physics).hits()?);
+        output(&scatter, svg.as_deref(), |scatter, out| {
+            scatter.write_svg(out)
+        })?;
+    }
+
+    println!("(day 17) part 1: {}", part1(&target, &physics)?);
+
+    println!("(day 17) part 2: {}", part2(&target, &physics)?);
 
     Ok(())
 }
 
-// Simply find maximum possible vertical velocity using the given velocity formula.
-// Then calculate height ('distance') by taking the sum of increasing integers.
-fn part1(area: &Area) -> i32 {
-    let yy = (area.min_y).abs() - 1;
+// Print the plot, unless it should be written to an SVG file instead
+fn output<T: Display>(
+    plot: &T,
+    svg: Option<&str>,
+    write_svg: impl Fn(&T, &mut BufWriter<File>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    match svg {
+        Some(path) => {
+            let file = File::create(path)
+                .with_context(|| anyhow!("Unable to create SVG file '{}'", path))?;
+            let mut writer = BufWriter::new(file);
+            write_svg(plot, &mut writer)?;
+            writer
+                .flush()
+                .with_context(|| anyhow!("Unable to flush SVG file '{}'", path))
+        }
+        None => {
+            println!("{}", plot);
+            Ok(())
+        }
+    }
+}
 
-    (yy * (yy + 1)) / 2
+// The highest point reached by any of the velocities which hit the target
+fn part1(area: &Area, physics: &Physics) -> anyhow::Result<i32> {
+    Solver::new(area, physics)
+        .hits()?
+        .iter()
+        .map(|velocity| physics.highest_point(velocity.y))
+        .max()
+        .with_context(|| anyhow!("No velocity hits the target area"))
 }
 
-// How to do this one more cleverly than simulating each 'ray'
-// At least we could try inverting the rays; but there's probably a way to calculate it?
-fn part2(area: &Area) -> usize {
-    Simulator::from_tuples((area.min_x - 90, area.max_x), (-150, 150)).run_simulations(area)
+// The amount of distinct velocities which hit the target
+fn part2(area: &Area, physics: &Physics) -> anyhow::Result<usize> {
+    Ok(Solver::new(area, physics).hits()?.len())
 }
 
-struct Simulator {
-    xx: (i32, i32),
-    yy: (i32, i32),
+/// The forces acting on the probe, and the position it's launched from.
+#[derive(Debug, Copy, Clone)]
+struct Physics {
+    // decrease of the vertical velocity, each step
+    gravity: i32,
+    // decrease of the horizontal velocity towards 0, each step
+    drag: i32,
+    origin: Position,
 }
 
-impl Simulator {
-    fn from_tuples(xx: (i32, i32), yy: (i32, i32)) -> Self {
-        Self { xx, yy }
+impl Default for Physics {
+    fn default() -> Self {
+        Self {
+            gravity: 1,
+            drag: 1,
+            origin: Position::default(),
+        }
     }
+}
 
-    fn run_simulations(&self, area: &Area) -> usize {
-        (self.xx.0..=self.xx.1)
-            .map(|vx| {
-                (self.yy.0..=self.yy.1)
-                    .filter(|&vy| Simulation::new(Step::new(Velocity::new(vx, vy))).simulate(area))
-                    .count()
-            })
-            .sum()
+impl Physics {
+    fn new(gravity: i32, drag: i32, origin: Position) -> anyhow::Result<Self> {
+        // without gravity pulling it down, the probe may never come down, and no search for
+        // velocities would be bounded
+        if gravity < 1 {
+            bail!("Gravity must be at least 1, but was {}", gravity);
+        }
+
+        if drag < 0 {
+            bail!("Drag can't be negative, but was {}", drag);
+        }
+
+        Ok(Self {
+            gravity,
+            drag,
+            origin,
+        })
+    }
+
+    fn apply_gravity(&self, vy: i32) -> i32 {
+        vy - self.gravity
+    }
+
+    // drag slows the probe down, but never reverses its direction
+    fn apply_drag(&self, vx: i32) -> i32 {
+        vx.signum() * (vx.abs() - self.drag).max(0)
+    }
+
+    // Only for a gravity of 1 this is a triangular number, so we let the probe go up instead
+    fn highest_point(&self, vy: i32) -> i32 {
+        let (mut y, mut v) = (self.origin.y, vy);
+
+        while v > 0 {
+            y += v;
+            v = self.apply_gravity(v);
+        }
+
+        y
+    }
+}
+
+/// Finds the velocities which hit the target, without simulating each of them.
+///
+/// The horizontal and vertical motion of the probe are independent, so for each horizontal
+/// velocity we can find the steps at which the probe is within the horizontal bounds of the
+/// target, and likewise for each vertical velocity. A velocity hits the target when its
+/// horizontal and vertical components share such a step.
+///
+/// The target is taken relative to the origin of the physics, so the probe is always launched
+/// from (0, 0).
+struct Solver<'p> {
+    area: Area,
+    physics: &'p Physics,
+}
+
+/// The steps at which the probe is within the bounds of the target along the x axis. Due to
+/// drag, the probe moves in a single direction, so these steps are consecutive. If the probe
+/// comes to a halt within the bounds, it stays there forever, and `last` is `None`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Steps {
+    first: usize,
+    last: Option<usize>,
+}
+
+impl Steps {
+    fn contains(&self, step: usize) -> bool {
+        step >= self.first && self.last.map(|last| step <= last).unwrap_or(true)
+    }
+}
+
+impl<'p> Solver<'p> {
+    fn new(area: &Area, physics: &'p Physics) -> Self {
+        let Position { x, y } = physics.origin;
+
+        Self {
+            area: Area::new(
+                (area.min_x - x, area.max_x - x),
+                (area.min_y - y, area.max_y - y),
+            ),
+            physics,
+        }
+    }
+
+    fn hits(&self) -> anyhow::Result<Vec<Velocity>> {
+        let area = &self.area;
+
+        // Any faster, and the first step overshoots the target. The probe has to move towards
+        // the target, unless it's already there.
+        let vx_range = area.min_x.min(0)..=area.max_x.max(0);
+
+        let xs = vx_range
+            .filter_map(|vx| self.x_steps(vx).map(|steps| (vx, steps)))
+            .collect::<Vec<_>>();
+
+        let vy_range = self.vy_range(&xs)?;
+
+        let mut hits = Vec::new();
+
+        for vy in vy_range {
+            let y_steps = self.y_steps(vy);
+
+            if y_steps.is_empty() {
+                continue;
+            }
+
+            hits.extend(
+                xs.iter()
+                    .filter(|(_, x_steps)| y_steps.iter().any(|&step| x_steps.contains(step)))
+                    .map(|&(vx, _)| Velocity::new(vx, vy)),
+            );
+        }
+
+        Ok(hits)
+    }
+
+    fn x_steps(&self, vx: i32) -> Option<Steps> {
+        let (mut x, mut v) = (0, vx);
+        let mut first = None;
+
+        for step in 1.. {
+            x += v;
+            v = self.physics.apply_drag(v);
+
+            let inside = (self.area.min_x..=self.area.max_x).contains(&x);
+
+            match (first, inside) {
+                (None, true) => first = Some(step),
+                (Some(first), false) => {
+                    return Some(Steps {
+                        first,
+                        last: Some(step - 1),
+                    })
+                }
+                _ => {}
+            }
+
+            // once the probe stops, it's either within bounds forever, or never again
+            if v == 0 {
+                return first.map(|first| Steps { first, last: None });
+            }
+
+            // without drag, the probe may keep moving, but never returns once it's past
+            let past = (v > 0 && x > self.area.max_x) || (v < 0 && x < self.area.min_x);
+            if past {
+                return None;
+            }
+        }
+
+        unreachable!()
+    }
+
+    /// The steps at which the probe is within the vertical bounds of the target. Going up,
+    /// and coming down again, the probe may pass through the target twice.
+    fn y_steps(&self, vy: i32) -> Vec<usize> {
+        let (mut y, mut v) = (0, vy);
+        let mut steps = Vec::new();
+
+        for step in 1.. {
+            y += v;
+            v = self.physics.apply_gravity(v);
+
+            if (self.area.min_y..=self.area.max_y).contains(&y) {
+                steps.push(step);
+            }
+
+            // falling, and below the target: the probe won't come back up
+            if v < 0 && y < self.area.min_y {
+                break;
+            }
+        }
+
+        steps
+    }
+
+    /// After `t` steps, with gravity `g`, the probe is at a height of
+    /// `y(t) = t * (2 * vy - g * (t - 1)) / 2`, which bounds the vertical velocity.
+    fn vy_range(&self, xs: &[(i32, Steps)]) -> anyhow::Result<RangeInclusive<i32>> {
+        let area = &self.area;
+        let g = self.physics.gravity;
+
+        // Any slower, and the first step already ends up below the target, never to return
+        let min = area.min_y.min(0);
+
+        let max = if area.max_y < 0 {
+            // Below y = 0, we have t > 2 * vy / g and 2 * vy - g * (t - 1) <= -1, so the
+            // probe is below -vy / g, which must not skip over the target
+            g * -area.min_y - 1
+        } else if area.min_y > 0 {
+            // Above y = 0, the height is concave in t, so the lowest heights are at the first
+            // step (vy), and at the last step going down, where the probe is at least vy / g
+            g * area.max_y
+        } else {
+            // The target contains y = 0, which the probe returns to whenever 2 * vy / g is a
+            // whole number. Unless the probe can also stay within the horizontal bounds
+            // forever, it's only within them for the first T steps. Faster than this, and
+            // the probe is above the target at steps 1 and T, and thus at any step between.
+            let last = xs
+                .iter()
+                .map(|(_, steps)| steps.last)
+                .try_fold(0, |acc, last| last.map(|last| acc.max(last)));
+
+            match last {
+                Some(last) => area.max_y + g * last as i32,
+                None => bail!(
+                    "Infinitely many velocities hit the target, as the probe can come to a \
+                     halt within its horizontal bounds, and pass through it at any height"
+                ),
+            }
+        };
+
+        Ok(min..=max)
     }
 }
 
@@ -53,20 +336,35 @@ struct Simulation {
 }
 
 impl Simulation {
-    fn new(initial_step: Step) -> Self {
-        Self { step: initial_step }
+    fn new(velocity: Velocity, physics: Physics) -> Self {
+        Self {
+            step: Step::new(velocity, physics),
+        }
     }
 
+    #[allow(unused)]
     fn simulate(&self, target: &Area) -> bool {
+        self.trajectory(target)
+            .iter()
+            .any(|&position| target.is_inside(position))
+    }
+
+    /// The positions of the probe, up to when it can no longer reach the target.
+    fn trajectory(&self, target: &Area) -> Vec<Position> {
         self.step
             .into_iter()
             .take_while(|step| {
-                let restriction_x = step.position.x <= target.max_x;
-                let restriction_y = step.position.y >= target.min_y;
+                let Position { x, y } = step.position;
+                let Velocity { x: vx, y: vy } = step.velocity;
+
+                // moving away from the target horizontally, or falling below it
+                let beyond_x = (vx >= 0 && x > target.max_x) || (vx <= 0 && x < target.min_x);
+                let below_y = vy < 0 && y < target.min_y;
 
-                restriction_x && restriction_y
+                !beyond_x && !below_y
             })
-            .any(|step| target.is_inside(step.position))
+            .map(|step| step.position)
+            .collect()
     }
 }
 
@@ -88,17 +386,25 @@ struct Position {
     y: i32,
 }
 
+impl Position {
+    fn new(x: i32, y: i32) -> Self {
+        Self { x, y }
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 struct Step {
     position: Position,
     velocity: Velocity,
+    physics: Physics,
 }
 
 impl Step {
-    fn new(velocity: Velocity) -> Self {
+    fn new(velocity: Velocity, physics: Physics) -> Self {
         Self {
             velocity,
-            position: Position::default(),
+            position: physics.origin,
+            physics,
         }
     }
 }
@@ -110,20 +416,14 @@ impl Iterator for Step {
         let Position { x: px, y: py } = self.position;
         let Velocity { x: vx, y: vy } = self.velocity;
 
-        let new_vx = match vx.cmp(&0) {
-            Ordering::Less => vx + 1,
-            Ordering::Greater => vx - 1,
-            Ordering::Equal => 0,
-        };
-
         self.position = Position {
             x: px + vx,
             y: py + vy,
         };
 
         self.velocity = Velocity {
-            x: new_vx,
-            y: vy - 1,
+            x: self.physics.apply_drag(vx),
+            y: self.physics.apply_gravity(vy),
         };
 
         Some(*self)
@@ -163,6 +463,188 @@ impl Area {
     }
 }
 
+/// Plots a single shot like the puzzle does: the launch position `S`, each position of the
+/// probe `#` and the target `T`, with up being up.
+struct PlotFmt<'a> {
+    target: &'a Area,
+    origin: Position,
+    trajectory: Vec<Position>,
+}
+
+impl<'a> PlotFmt<'a> {
+    fn new(target: &'a Area, simulation: &Simulation) -> Self {
+        Self {
+            target,
+            origin: simulation.step.position,
+            trajectory: simulation.trajectory(target),
+        }
+    }
+
+    // Everything which is plotted
+    fn bounds(&self) -> Area {
+        let xs = self.trajectory.iter().map(|p| p.x);
+        let ys = self.trajectory.iter().map(|p| p.y);
+        let (target, origin) = (self.target, self.origin);
+
+        let xs = xs.chain([origin.x, target.min_x, target.max_x]);
+        let ys = ys.chain([origin.y, target.min_y, target.max_y]);
+
+        Area::new(
+            (xs.clone().min().unwrap(), xs.max().unwrap()),
+            (ys.clone().min().unwrap(), ys.max().unwrap()),
+        )
+    }
+
+    fn write_svg<W: Write>(&self, output: &mut W) -> anyhow::Result<()> {
+        let bounds = self.bounds();
+        let (target, origin) = (self.target, self.origin);
+
+        let points = std::iter::once(origin)
+            .chain(self.trajectory.iter().copied())
+            .map(|p| format!("{},{}", p.x, -p.y))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let markers = self
+            .trajectory
+            .iter()
+            .map(|p| format!("M{} {}h0", p.x, -p.y))
+            .collect::<String>();
+
+        write_svg_header(output, &bounds)?;
+        writeln!(
+            output,
+            r##"<rect x="{}" y="{}" width="{}" height="{}" fill="#e0a030" fill-opacity="0.5"/>
+<polyline points="{}" fill="none" stroke="#606060" stroke-width="1" vector-effect="non-scaling-stroke"/>
+<path d="{}" stroke="#2060c0" stroke-width="6" stroke-linecap="round" vector-effect="non-scaling-stroke"/>
+<path d="M{} {}h0" stroke="#20a040" stroke-width="10" stroke-linecap="round" vector-effect="non-scaling-stroke"/>
+</svg>"##,
+            target.min_x,
+            -target.max_y,
+            target.max_x - target.min_x,
+            target.max_y - target.min_y,
+            points,
+            markers,
+            origin.x,
+            -origin.y,
+        )
+        .with_context(|| anyhow!("Unable to write SVG"))
+    }
+}
+
+impl Display for PlotFmt<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let bounds = self.bounds();
+        let trajectory = self
+            .trajectory
+            .iter()
+            .map(|p| (p.x, p.y))
+            .collect::<HashSet<_>>();
+
+        for y in (bounds.min_y..=bounds.max_y).rev() {
+            for x in bounds.min_x..=bounds.max_x {
+                let c = if (x, y) == (self.origin.x, self.origin.y) {
+                    'S'
+                } else if trajectory.contains(&(x, y)) {
+                    '#'
+                } else if self.target.is_inside(Position::new(x, y)) {
+                    'T'
+                } else {
+                    '.'
+                };
+
+                f.write_fmt(format_args!("{}", c))?;
+            }
+
+            f.write_str("\n")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Plots the velocities which hit the target, with vx to the right and vy up. The axes, where
+/// no velocity hits, are drawn with `|`, `-` and `+`.
+struct ScatterFmt {
+    hits: Vec<Velocity>,
+}
+
+impl ScatterFmt {
+    fn new(hits: Vec<Velocity>) -> Self {
+        Self { hits }
+    }
+
+    // The velocities, and the axes
+    fn bounds(&self) -> Area {
+        let xs = self.hits.iter().map(|v| v.x).chain([0]);
+        let ys = self.hits.iter().map(|v| v.y).chain([0]);
+
+        Area::new(
+            (xs.clone().min().unwrap(), xs.max().unwrap()),
+            (ys.clone().min().unwrap(), ys.max().unwrap()),
+        )
+    }
+
+    fn write_svg<W: Write>(&self, output: &mut W) -> anyhow::Result<()> {
+        let bounds = self.bounds();
+
+        let markers = self
+            .hits
+            .iter()
+            .map(|v| format!("M{} {}h0", v.x, -v.y))
+            .collect::<String>();
+
+        write_svg_header(output, &bounds)?;
+        writeln!(
+            output,
+            r##"<path d="M{} 0H{}M0 {}V{}" stroke="#a0a0a0" stroke-width="1" vector-effect="non-scaling-stroke"/>
+<path d="{}" stroke="#2060c0" stroke-width="4" stroke-linecap="round" vector-effect="non-scaling-stroke"/>
+</svg>"##,
+            bounds.min_x, bounds.max_x, -bounds.max_y, -bounds.min_y, markers,
+        )
+        .with_context(|| anyhow!("Unable to write SVG"))
+    }
+}
+
+impl Display for ScatterFmt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let bounds = self.bounds();
+        let hits = self.hits.iter().map(|v| (v.x, v.y)).collect::<HashSet<_>>();
+
+        for vy in (bounds.min_y..=bounds.max_y).rev() {
+            for vx in bounds.min_x..=bounds.max_x {
+                let c = match (hits.contains(&(vx, vy)), vx, vy) {
+                    (true, _, _) => '#',
+                    (false, 0, 0) => '+',
+                    (false, 0, _) => '|',
+                    (false, _, 0) => '-',
+                    _ => '.',
+                };
+
+                f.write_fmt(format_args!("{}", c))?;
+            }
+
+            f.write_str("\n")?;
+        }
+
+        Ok(())
+    }
+}
+
+// The y axis of an SVG points down, so everything is drawn at -y. Since heights can be far
+// larger than widths, the plot is stretched to a fixed size.
+fn write_svg_header<W: Write>(output: &mut W, bounds: &Area) -> anyhow::Result<()> {
+    writeln!(
+        output,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="800" height="600" viewBox="{} {} {} {}" preserveAspectRatio="none">"#,
+        bounds.min_x - 1,
+        -bounds.max_y - 1,
+        bounds.max_x - bounds.min_x + 2,
+        bounds.max_y - bounds.min_y + 2,
+    )
+    .with_context(|| anyhow!("Unable to write SVG header"))
+}
+
 fn parse(input: &str) -> Area {
     let (x_side, y_side) = input.trim().split_once(", ").unwrap();
     let (xh, xr) = x_side.split_once("..").unwrap();
@@ -181,14 +663,17 @@ fn parse(input: &str) -> Area {
 
 #[cfg(test)]
 mod tests {
-    use crate::{parse, part1, part2};
+    use crate::{
+        parse, part1, part2, Physics, PlotFmt, Position, ScatterFmt, Simulation, Solver, Velocity,
+    };
+    use std::collections::HashSet;
 
     #[test]
     fn part1_example() {
         let input = include_str!("../../inputs/example/day17.txt");
         let area = parse(input);
 
-        assert_eq!(part1(&area), 45);
+        assert_eq!(part1(&area, &Physics::default()).unwrap(), 45);
     }
 
     #[test]
@@ -196,7 +681,7 @@ mod tests {
         let input = include_str!("../../inputs/day17.txt");
         let area = parse(input);
 
-        assert_eq!(part1(&area), 10296);
+        assert_eq!(part1(&area, &Physics::default()).unwrap(), 10296);
     }
 
     #[test]
@@ -204,7 +689,7 @@ mod tests {
         let input = include_str!("../../inputs/example/day17.txt");
         let area = parse(input);
 
-        assert_eq!(part2(&area), 112);
+        assert_eq!(part2(&area, &Physics::default()).unwrap(), 112);
     }
 
     #[test]
@@ -212,6 +697,182 @@ mod tests {
         let input = include_str!("../../inputs/day17.txt");
         let area = parse(input);
 
-        assert_eq!(part2(&area), 2371);
+        assert_eq!(part2(&area, &Physics::default()).unwrap(), 2371);
+    }
+
+    // brute force, by simulating every velocity in a generous range
+    fn simulated(input: &str, physics: Physics) -> HashSet<(i32, i32)> {
+        let area = parse(input);
+
+        (-80..=80)
+            .flat_map(|vx| (-80..=80).map(move |vy| (vx, vy)))
+            .filter(|&(vx, vy)| Simulation::new(Velocity::new(vx, vy), physics).simulate(&area))
+            .collect()
+    }
+
+    fn solved(input: &str, physics: Physics) -> HashSet<(i32, i32)> {
+        let area = parse(input);
+
+        Solver::new(&area, &physics)
+            .hits()
+            .unwrap()
+            .iter()
+            .map(|velocity| (velocity.x, velocity.y))
+            .collect()
+    }
+
+    #[test]
+    fn solver_agrees_with_simulation() {
+        for input in [
+            // below and to the right, like the puzzle
+            "target area: x=20..30, y=-10..-5",
+            // to the left
+            "target area: x=-30..-20, y=-10..-5",
+            // above
+            "target area: x=20..30, y=5..10",
+            "target area: x=-30..-20, y=12..20",
+            // straight below, or straight above
+            "target area: x=-3..4, y=-10..-5",
+            "target area: x=-3..4, y=5..10",
+            // at the height of the launch point, so the probe may not come to a halt in it
+            "target area: x=37..44, y=-5..5",
+        ] {
+            let solved = solved(input, Physics::default());
+
+            assert!(!solved.is_empty(), "{}", input);
+            assert_eq!(solved, simulated(input, Physics::default()), "{}", input);
+        }
+    }
+
+    #[test]
+    fn solver_agrees_with_simulation_under_other_physics() {
+        let physics = [
+            Physics::new(2, 1, Position::default()).unwrap(),
+            Physics::new(3, 2, Position::default()).unwrap(),
+            Physics::new(1, 0, Position::default()).unwrap(),
+            Physics::new(1, 1, Position::new(-10, 4)).unwrap(),
+            Physics::new(2, 3, Position::new(5, -20)).unwrap(),
+        ];
+
+        for physics in physics {
+            for input in [
+                "target area: x=20..30, y=-10..-5",
+                "target area: x=-30..-20, y=-10..-5",
+                "target area: x=20..30, y=5..10",
+                "target area: x=-3..4, y=-10..-5",
+                "target area: x=-30..-20, y=12..20",
+            ] {
+                let solved = solved(input, physics);
+                let simulated = simulated(input, physics);
+
+                assert_eq!(solved, simulated, "{} with {:?}", input, physics);
+            }
+        }
+    }
+
+    #[test]
+    fn part1_other_physics() {
+        let input = include_str!("../../inputs/example/day17.txt");
+        let area = parse(input);
+
+        // with a gravity of 2, going up with vy = 8 gives 8 + 6 + 4 + 2 = 20, and coming down
+        // the probe lands at -10
+        let physics = Physics::new(2, 1, Position::default()).unwrap();
+        assert_eq!(part1(&area, &physics).unwrap(), 20);
+
+        // launched from higher up, the probe can go up faster too
+        let physics = Physics::new(1, 1, Position::new(0, 5)).unwrap();
+        assert_eq!(part1(&area, &physics).unwrap(), 5 + 14 * 15 / 2);
+    }
+
+    #[test]
+    fn invalid_physics() {
+        assert!(Physics::new(0, 1, Position::default()).is_err());
+        assert!(Physics::new(1, -1, Position::default()).is_err());
+    }
+
+    #[test]
+    fn part1_above() {
+        // the highest point is reached when going up with vy = 10, landing right at the top
+        // of the target on the way down
+        let area = parse("target area: x=20..30, y=5..10");
+        assert_eq!(part1(&area, &Physics::default()).unwrap(), 55);
+
+        let area = parse("target area: x=-30..-20, y=-10..-5");
+        assert_eq!(part1(&area, &Physics::default()).unwrap(), 45);
+    }
+
+    #[test]
+    fn infinitely_many() {
+        let area = parse("target area: x=-3..4, y=-5..5");
+        assert!(Solver::new(&area, &Physics::default()).hits().is_err());
+
+        // with vx = 7, the probe halts at x = 28
+        let area = parse("target area: x=20..30, y=-5..5");
+        assert!(Solver::new(&area, &Physics::default()).hits().is_err());
+    }
+
+    #[test]
+    fn plot_example() {
+        let input = include_str!("../../inputs/example/day17.txt");
+        let area = parse(input);
+
+        let simulation = Simulation::new(Velocity::new(7, 2), Physics::default());
+        let plot = PlotFmt::new(&area, &simulation).to_string();
+
+        let expected = "\
+.............#....#............
+.......#..............#........
+...............................
+S........................#.....
+...............................
+...............................
+...........................#...
+...............................
+....................TTTTTTTTTTT
+....................TTTTTTTTTTT
+....................TTTTTTTT#TT
+....................TTTTTTTTTTT
+....................TTTTTTTTTTT
+....................TTTTTTTTTTT
+";
+
+        assert_eq!(plot, expected);
+    }
+
+    #[test]
+    fn plot_svg() {
+        let input = include_str!("../../inputs/example/day17.txt");
+        let area = parse(input);
+
+        let simulation = Simulation::new(Velocity::new(6, 3), Physics::default());
+        let mut svg = Vec::new();
+        PlotFmt::new(&area, &simulation)
+            .write_svg(&mut svg)
+            .unwrap();
+
+        let svg = String::from_utf8(svg).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains(r#"viewBox="-1 -7 32 18""#));
+        assert!(svg.contains(r#"<polyline points="0,0 6,-3 11,-5 15,-6 18,-6"#));
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+
+    #[test]
+    fn scatter() {
+        let input = "target area: x=2..3, y=-2..-1";
+        let area = parse(input);
+
+        let hits = Solver::new(&area, &Physics::default()).hits().unwrap();
+        let scatter = ScatterFmt::new(hits).to_string();
+
+        let expected = "\
+|.#.
++-#-
+|.##
+|.##
+";
+
+        assert_eq!(scatter, expected);
     }
 }